@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::database::{Database, Transaction};
+use crate::error::QuestError;
+use crate::quest::Status;
+
+/// Moves finished quest chains out of the quest log into its archive database, and stores the
+/// automatic archiving setting.
+pub struct ArchiveDao<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ArchiveDao<'a> {
+    /// The setting holding the age after which finished chains are archived automatically.
+    const AUTO_ARCHIVE_SETTING: &'static str = "auto_archive";
+
+    /// Constructs a new archive data access object.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Moves every finished main quest chain, whose quests were all last updated before the
+    /// specified time, into the archive, and returns the number of chains moved. A chain is
+    /// finished once every quest in it is completed or abandoned. Quests keep their identifiers,
+    /// unless the archive already holds a quest with the same identifier. Operations in the undo
+    /// journal that touched the archived quests, and every operation before them, are forgotten,
+    /// since they can no longer be replayed. Tags left unused are deleted, unless a remaining
+    /// operation would put them back on a quest.
+    pub fn archive(
+        &self,
+        archive: &Database,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<usize, QuestError> {
+        // Create the archive, and upgrade its schema, before attaching it.
+        archive.conn()?;
+
+        let path = archive.path().to_string_lossy().into_owned();
+        self.conn.execute("ATTACH DATABASE ?1 AS archive", [path])?;
+        let archived = self.archive_attached(before);
+        self.conn.execute("DETACH DATABASE archive", [])?;
+
+        archived
+    }
+
+    /// Gets the age after which finished chains are archived automatically, if set.
+    pub fn get_auto_archive(&self) -> Result<Option<String>, QuestError> {
+        let age = self
+            .conn
+            .query_row(
+                "SELECT value FROM setting WHERE name = ?1",
+                [Self::AUTO_ARCHIVE_SETTING],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(age)
+    }
+
+    /// Sets or clears the age after which finished chains are archived automatically.
+    pub fn set_auto_archive(&self, age: Option<&str>) -> Result<(), QuestError> {
+        match age {
+            Some(age) => self.conn.execute(
+                "INSERT INTO setting (name, value) VALUES (?1, ?2)
+                ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+                params![Self::AUTO_ARCHIVE_SETTING, age],
+            )?,
+            None => self.conn.execute(
+                "DELETE FROM setting WHERE name = ?1",
+                [Self::AUTO_ARCHIVE_SETTING],
+            )?,
+        };
+
+        Ok(())
+    }
+
+    /// Moves the finished chains into the attached archive, inside one transaction.
+    fn archive_attached(&self, before: Option<DateTime<Utc>>) -> Result<usize, QuestError> {
+        let tx = Transaction::new(self.conn)?;
+
+        // Find the finished main quests. Quests in the trash neither keep a chain open nor stay
+        // behind, since they would lose their parent.
+        let main_quest_ids = tx
+            .prepare(
+                "WITH RECURSIVE chain (root_id, id) AS (
+                    SELECT id, id FROM main.quest WHERE chain_id IS NULL AND deleted_at IS NULL
+                    UNION
+                    SELECT chain.root_id, quest.id FROM main.quest
+                    INNER JOIN chain ON quest.chain_id = chain.id
+                )
+                SELECT chain.root_id FROM chain
+                INNER JOIN main.quest ON quest.id = chain.id
+                GROUP BY chain.root_id
+                HAVING SUM(quest.deleted_at IS NULL AND quest.status NOT IN (?1, ?2)) = 0
+                AND (?3 IS NULL OR IFNULL(MAX(quest.updated_at), '') < ?3)
+                ORDER BY chain.root_id",
+            )?
+            .query_map(
+                params![Status::Completed as i64, Status::Abandoned as i64, before],
+                |row| row.get(0),
+            )?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+
+        let mut quest_ids = Vec::new();
+        for &main_quest_id in &main_quest_ids {
+            quest_ids.extend(Self::copy_chain(&tx, main_quest_id)?);
+        }
+
+        if quest_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // Remove the archived quests from the log, along with everything that refers to them. Tags
+        // are only deleted once neither a quest nor the rest of the undo journal refers to them.
+        let quest_ids = quest_ids
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        tx.execute_batch(&format!(
+            "DELETE FROM undo_operation WHERE id <= (
+                SELECT MAX(operation_id) FROM undo_change
+                WHERE json_extract(
+                    IFNULL(after, before),
+                    CASE table_name WHEN 'quest' THEN '$.id' ELSE '$.quest_id' END
+                ) IN ({0})
+            );
+            DELETE FROM main.note WHERE quest_id IN ({0});
+            DELETE FROM main.quest_transition WHERE quest_id IN ({0});
+            DELETE FROM main.quest_tag WHERE quest_id IN ({0});
+            DELETE FROM main.quest WHERE id IN ({0});
+            DELETE FROM main.tag WHERE id NOT IN (SELECT tag_id FROM main.quest_tag)
+            AND id NOT IN (
+                SELECT json_extract(before, '$.tag_id') FROM undo_change
+                WHERE table_name = 'quest_tag' AND before IS NOT NULL
+                UNION
+                SELECT json_extract(after, '$.tag_id') FROM undo_change
+                WHERE table_name = 'quest_tag' AND after IS NOT NULL
+            );",
+            quest_ids
+        ))?;
+
+        tx.commit()?;
+        Ok(main_quest_ids.len())
+    }
+
+    /// Copies a quest chain, with its history, notes, and tags, into the attached archive, parents
+    /// first. Returns the identifiers of the copied quests in the log.
+    fn copy_chain(conn: &Connection, main_quest_id: i64) -> Result<Vec<i64>, QuestError> {
+        let quests = conn
+            .prepare(
+                "WITH RECURSIVE chain (id, chain_id, depth) AS (
+                    SELECT id, NULL, 0 FROM main.quest WHERE id = ?1
+                    UNION
+                    SELECT quest.id, quest.chain_id, chain.depth + 1 FROM main.quest
+                    INNER JOIN chain ON quest.chain_id = chain.id
+                )
+                SELECT id, chain_id FROM chain ORDER BY depth, id",
+            )?
+            .query_map([main_quest_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, Option<i64>)>>>()?;
+
+        // Maps the identifier of each quest in the log to its identifier in the archive.
+        let mut archive_ids: HashMap<i64, i64> = HashMap::new();
+
+        for &(quest_id, chain_id) in &quests {
+            let is_taken = conn
+                .prepare("SELECT 1 FROM archive.quest WHERE id = ?1")?
+                .exists([quest_id])?;
+            let archive_id = if is_taken { None } else { Some(quest_id) };
+            let archive_chain_id = chain_id.and_then(|chain_id| archive_ids.get(&chain_id));
+
+            conn.execute(
+                "INSERT INTO archive.quest (
+                    id, chain_id, objective, status, tier, due_date, start_date,
+                    created_at, updated_at, deleted_at
+                )
+                SELECT
+                    ?2, ?3, objective, status, tier, due_date, start_date,
+                    created_at, updated_at, deleted_at
+                FROM main.quest WHERE id = ?1",
+                params![quest_id, archive_id, archive_chain_id],
+            )?;
+            let archive_id = conn.last_insert_rowid();
+            archive_ids.insert(quest_id, archive_id);
+
+            conn.execute(
+                "INSERT INTO archive.quest_transition (quest_id, status, changed_at)
+                SELECT ?2, status, changed_at FROM main.quest_transition
+                WHERE quest_id = ?1 ORDER BY id",
+                [quest_id, archive_id],
+            )?;
+            conn.execute(
+                "INSERT INTO archive.note (quest_id, text, created_at)
+                SELECT ?2, text, created_at FROM main.note
+                WHERE quest_id = ?1 ORDER BY id",
+                [quest_id, archive_id],
+            )?;
+            conn.execute(
+                "INSERT OR IGNORE INTO archive.tag (name)
+                SELECT tag.name FROM main.quest_tag
+                INNER JOIN main.tag ON tag.id = quest_tag.tag_id
+                WHERE quest_tag.quest_id = ?1",
+                [quest_id],
+            )?;
+            conn.execute(
+                "INSERT INTO archive.quest_tag (quest_id, tag_id)
+                SELECT ?2, archive_tag.id FROM main.quest_tag
+                INNER JOIN main.tag ON tag.id = quest_tag.tag_id
+                INNER JOIN archive.tag AS archive_tag ON archive_tag.name = tag.name
+                WHERE quest_tag.quest_id = ?1",
+                [quest_id, archive_id],
+            )?;
+        }
+
+        Ok(quests.iter().map(|&(quest_id, _)| quest_id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::log::NoteDao;
+    use crate::quest::{Quest, QuestDao};
+    use crate::testing::{add, open};
+    use crate::undo::UndoDao;
+
+    /// An archive in the temporary directory, removed once dropped.
+    struct TempArchive(Database);
+
+    impl TempArchive {
+        fn new(name: &str) -> Self {
+            let file_name = format!("quest-{}-archive-{}.sqlite", process::id(), name);
+            let path = env::temp_dir().join(file_name);
+            let _ = fs::remove_file(&path);
+            Self(Database::at(path).unwrap())
+        }
+    }
+
+    impl Drop for TempArchive {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(self.0.path());
+        }
+    }
+
+    /// Applies a change to a quest as one undoable operation.
+    fn edit(conn: &Connection, quest_id: i64, change: impl FnOnce(&mut Quest)) {
+        let quest_dao = QuestDao::new(conn);
+        let mut quest = quest_dao.get_quest(quest_id).unwrap();
+        change(&mut quest);
+
+        let recording = UndoDao::new(conn).begin("edit a quest").unwrap();
+        quest_dao.update_quest(&quest).unwrap();
+        recording.commit().unwrap();
+    }
+
+    fn count(conn: &Connection, query: &str) -> i64 {
+        conn.query_row(query, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn archive_renumbers_quests_already_in_the_archive() {
+        let archive = TempArchive::new("collision");
+        {
+            let conn = archive.0.conn().unwrap();
+            add(&conn, "Archived", None);
+            add(&conn, "Archived too", None);
+        }
+
+        let conn = open();
+        let main_id = add(&conn, "Main", None);
+        let secondary_id = add(&conn, "Secondary", Some(main_id));
+        let open_id = add(&conn, "Open", None);
+        edit(&conn, secondary_id, |quest| {
+            *quest.status_mut() = Status::Abandoned;
+            quest.tags_mut().push("boss".to_owned());
+        });
+        edit(&conn, main_id, |quest| {
+            *quest.status_mut() = Status::Completed
+        });
+        NoteDao::new(&conn)
+            .add_note(secondary_id, "Too strong for now.")
+            .unwrap();
+
+        assert_eq!(ArchiveDao::new(&conn).archive(&archive.0, None).unwrap(), 1);
+
+        // Only the open quest stays in the log, and nothing refers to the others anymore.
+        let quest_dao = QuestDao::new(&conn);
+        let chains = quest_dao.get_all_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].id(), open_id);
+        assert_eq!(count(&conn, "SELECT COUNT() FROM tag"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT() FROM note"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT() FROM quest_transition"), 1);
+
+        // The identifiers are taken in the archive, so the chain gets new ones, and still links
+        // up.
+        let archive_conn = archive.0.conn().unwrap();
+        let archive_dao = QuestDao::new(&archive_conn);
+        let chains = archive_dao.get_all_chains().unwrap();
+        assert_eq!(chains.len(), 3);
+        assert_eq!(chains[0].objective(), "Archived");
+        assert_eq!(chains[1].objective(), "Archived too");
+
+        let chain = &chains[2];
+        assert_eq!(chain.id(), 3);
+        assert_eq!(chain.objective(), "Main");
+        assert_eq!(chain.status(), Status::Completed);
+        assert_eq!(chain.chains().len(), 1);
+
+        // The secondary quest keeps its tags, notes, and history.
+        let secondary = chain.chains()[0].main();
+        assert_eq!(secondary.id(), 4);
+        assert_eq!(secondary.status(), Status::Abandoned);
+        assert_eq!(secondary.tags(), &vec!["boss".to_owned()]);
+
+        let notes = NoteDao::new(&archive_conn).get_notes(4).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].text(), "Too strong for now.");
+
+        let history: Vec<Status> = archive_dao
+            .get_history(4)
+            .unwrap()
+            .iter()
+            .map(|transition| transition.status())
+            .collect();
+        assert_eq!(history, vec![Status::Pending, Status::Abandoned]);
+    }
+
+    #[test]
+    fn archive_keeps_tags_the_undo_journal_still_refers_to() {
+        let archive = TempArchive::new("undo");
+
+        let conn = open();
+        let done_id = add(&conn, "Done", None);
+        edit(&conn, done_id, |quest| {
+            quest.tags_mut().push("boss".to_owned())
+        });
+        edit(&conn, done_id, |quest| {
+            *quest.status_mut() = Status::Completed
+        });
+
+        // These operations come after the archived quest was last touched, so they are kept.
+        let quest_id = add(&conn, "Open", None);
+        edit(&conn, quest_id, |quest| {
+            quest.tags_mut().push("boss".to_owned())
+        });
+        edit(&conn, quest_id, |quest| quest.tags_mut().clear());
+
+        assert_eq!(ArchiveDao::new(&conn).archive(&archive.0, None).unwrap(), 1);
+        assert_eq!(count(&conn, "SELECT COUNT() FROM tag"), 1);
+
+        // Putting the tag back on the open quest needs the tag row to still be there.
+        let undo_dao = UndoDao::new(&conn);
+        assert!(undo_dao.undo().unwrap().is_some());
+        let quest_dao = QuestDao::new(&conn);
+        assert_eq!(
+            quest_dao.get_quest(quest_id).unwrap().tags(),
+            &vec!["boss".to_owned()]
+        );
+
+        assert!(undo_dao.undo().unwrap().is_some());
+        assert!(undo_dao.undo().unwrap().is_some());
+        assert!(undo_dao.undo().unwrap().is_none());
+        assert!(quest_dao.get_all_chains().unwrap().is_empty());
+    }
+}
@@ -1,25 +1,76 @@
-use std::io::{stdin, stdout, Write};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{self, stdin, stdout, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{self, ExitCode};
+use std::{env, fs};
 
+use chrono::{DateTime, NaiveDate, Utc};
 use clap::builder::styling::AnsiColor;
 use clap::builder::Styles;
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
+use serde::Serialize;
 
-use crate::database::Database;
-use crate::quest::{Chain, Quest, QuestDao, Status, Tier};
-use crate::table::{Cell, Table};
+use crate::archive::ArchiveDao;
+use crate::database::{self, Database};
+use crate::date::{self, DATE_FORMAT};
+use crate::doctor::{Doctor, OrphanFix};
+use crate::error::QuestError;
+use crate::export::{self, Exporter, Format};
+use crate::import::Importer;
+use crate::log::{Note, NoteDao};
+use crate::quest::{Chain, Quest, QuestDao, QuestFilter, Sort, Status, Tier};
+use crate::table::{self, Cell, Table};
+use crate::tag::{self, TagDao};
+use crate::undo::UndoDao;
 
 /// Default styles.
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Yellow.on_default())
     .usage(AnsiColor::Yellow.on_default());
 
+/// Exit codes, documented in the long help for scripts to check.
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  2  Invalid command line usage
+  3  Quest not found
+  4  Invalid parent quest
+  5  Quest log could not be accessed
+  6  Quest log contains a corrupt value
+  7  Text editor could not be run
+  8  Quest log does not exist
+  9  Imported quests are invalid or conflict with the log
+ 10  Quest log has no quests";
+
 /// Create and manage projects, set timers, and more!
 #[derive(Parser)]
-#[command(long_about, styles = STYLES, version)]
+#[command(after_long_help = EXIT_CODES, long_about, styles = STYLES, version)]
 pub struct Args {
     #[command(subcommand)]
     command: Command,
+
+    /// Path of the quest log database [env: QUEST_DB]
+    #[arg(global = true, long, value_name = "PATH")]
+    db: Option<PathBuf>,
+
+    /// Name of the quest log to use, created with `quest logs create`
+    #[arg(conflicts_with = "db", global = true, long, value_parser = database::parse_log_name)]
+    log: Option<String>,
+
+    /// Use the user-wide quest log, even inside a project with its own log
+    #[arg(conflicts_with_all = ["db", "log"], global = true, long)]
+    global: bool,
+
+    /// Output format of `log`, `show`, `add`, and the commands that change a quest's status; other
+    /// commands only write text
+    #[arg(default_value_t = Output::Text, global = true, long, value_enum)]
+    output: Output,
 }
 
 /// Args implementation.
@@ -27,6 +78,51 @@ impl Args {
     fn command(&self) -> Command {
         self.command.clone()
     }
+
+    /// Checks that the command supports the output format, since `--output` is accepted by every
+    /// command.
+    fn check_output(&self) -> Result<(), clap::Error> {
+        let supports_json = matches!(
+            self.command,
+            Command::Abandon { .. }
+                | Command::Accept { .. }
+                | Command::Add(_)
+                | Command::Complete { .. }
+                | Command::Log(_)
+                | Command::Modify(_)
+                | Command::Show { .. }
+        );
+
+        if self.output == Output::Json && !supports_json {
+            return Err(<Self as CommandFactory>::command().error(
+                ErrorKind::ArgumentConflict,
+                "`--output json` is only supported by `log`, `show`, `add`, `accept`, `abandon`, \
+                `complete`, and `modify`",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A format for command output.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Output {
+    /// Human-readable text and tables
+    Text,
+    /// JSON, for scripts and editor plugins
+    Json,
+}
+
+/// The details of a quest shown by `quest show`.
+#[derive(Serialize)]
+struct QuestDetails<'a> {
+    #[serde(flatten)]
+    quest: &'a Quest,
+    ancestors: &'a [Quest],
+    quests: &'a [Quest],
+    subtree: HashMap<Status, i64>,
+    notes: &'a [Note],
 }
 
 /// Represents every possible `quest` command.
@@ -48,21 +144,22 @@ enum Command {
 
     /// Add a quest
     #[command(long_about)]
-    Add {
-        /// Objective
-        objective: String,
+    Add(AddArgs),
 
-        /// Status
-        #[arg(default_value_t = Status::Pending, long, short, value_enum)]
-        status: Status,
+    /// Move finished quest chains out of the log into its archive
+    #[command(long_about)]
+    Archive {
+        /// Only archive chains last updated more than this long ago, e.g. 30d, 2w, or 1m
+        #[arg(long, value_name = "AGE", value_parser = date::parse_age)]
+        older_than: Option<NaiveDate>,
 
-        /// Tier
-        #[arg(default_value_t = Tier::Common, long, short, value_enum)]
-        tier: Tier,
+        /// Archive chains finished this long ago whenever the log is shown
+        #[arg(conflicts_with = "older_than", long, value_name = "AGE", value_parser = date::validate_age)]
+        auto: Option<String>,
 
-        /// Create a quest chain
-        #[arg(long = "sub", value_name = "QUEST_ID")]
-        chain_id: Option<i64>,
+        /// Stop archiving chains automatically
+        #[arg(conflicts_with_all = ["older_than", "auto"], long)]
+        no_auto: bool,
     },
 
     /// Complete a quest
@@ -79,28 +176,268 @@ enum Command {
         quest_id: i64,
     },
 
+    /// Check the quest log for problems, and optionally fix them
+    #[command(long_about)]
+    Doctor {
+        /// Fix the problems found, which cannot be undone and clears the undo history
+        #[arg(long)]
+        fix: bool,
+
+        /// How to fix quests whose parent quest does not exist
+        #[arg(default_value_t = OrphanFix::Reroot, long, requires = "fix", value_enum)]
+        orphans: OrphanFix,
+    },
+
+    /// Export every quest in a documented interchange format
+    #[command(after_long_help = export::SCHEMA, long_about)]
+    Export {
+        /// Format
+        #[arg(default_value = "json", long, short, value_parser = Format::export_parser())]
+        format: Format,
+    },
+
+    /// Show the timeline of a quest
+    #[command(long_about)]
+    History {
+        /// Quest ID
+        quest_id: i64,
+    },
+
+    /// Import quests written by `quest export`, todo.txt, calendar apps, or Taskwarrior
+    #[command(after_long_help = export::SCHEMA, long_about)]
+    Import {
+        /// File to import, or - for standard input
+        file: PathBuf,
+
+        /// Format [default: guessed from the file extension, or json]
+        #[arg(long, short, value_enum)]
+        format: Option<Format>,
+
+        /// Skip quests that are already in the log, instead of refusing the import
+        #[arg(long)]
+        merge: bool,
+
+        /// Check the file and count the quests to import, without changing the log
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Create a project quest log in the current directory
+    #[command(long_about)]
+    Init,
+
     /// Show all quests  
     #[command(long_about)]
-    Log,
+    Log(LogArgs),
+
+    /// Manage named quest logs
+    #[command(long_about)]
+    Logs {
+        #[command(subcommand)]
+        command: LogsCommand,
+    },
 
     /// Modify a quest
     #[command(long_about)]
-    Modify {
+    Modify(ModifyArgs),
+
+    /// Move a quest, and its secondary quests, to another chain
+    #[command(long_about)]
+    Move {
         /// Quest ID
         quest_id: i64,
 
-        /// Objective
-        #[arg(long, short, value_enum)]
-        objective: Option<String>,
+        /// New parent quest
+        #[arg(long, required_unless_present = "root", value_name = "QUEST_ID")]
+        to: Option<i64>,
 
-        /// Status
-        #[arg(long, short, value_enum)]
-        status: Option<Status>,
+        /// Promote the quest to a main quest
+        #[arg(conflicts_with = "to", long)]
+        root: bool,
+    },
 
-        /// Tier
-        #[arg(long, short, value_enum)]
-        tier: Option<Tier>,
+    /// Add a note to the journal of a quest
+    #[command(long_about)]
+    Note {
+        /// Quest ID
+        quest_id: i64,
+
+        /// Markdown text. Opens $EDITOR if omitted
+        text: Option<String>,
     },
+
+    /// Permanently delete the quests in the trash
+    #[command(long_about)]
+    Purge {
+        /// Only purge quests deleted more than this long ago, e.g. 30d, 2w, or 1m
+        #[arg(long, value_name = "AGE", value_parser = date::parse_age)]
+        older_than: Option<NaiveDate>,
+    },
+
+    /// Redo the last undone command
+    #[command(long_about)]
+    Redo,
+
+    /// Restore a quest, and its secondary quests, from the trash
+    #[command(long_about)]
+    Restore {
+        /// Quest ID
+        quest_id: i64,
+    },
+
+    /// Show the details and journal of a quest
+    #[command(long_about)]
+    Show {
+        /// Quest ID
+        quest_id: i64,
+    },
+
+    /// Show all tags
+    #[command(long_about)]
+    Tags,
+
+    /// Show the quests in the trash
+    #[command(long_about)]
+    Trash,
+
+    /// Undo the last command that changed the quest log
+    #[command(long_about)]
+    Undo,
+}
+
+/// Represents every `quest logs` command.
+#[derive(Clone, Subcommand)]
+enum LogsCommand {
+    /// Create a named quest log
+    Create {
+        /// Log name
+        #[arg(value_parser = database::parse_log_name)]
+        name: String,
+    },
+
+    /// Show all quest logs
+    List,
+
+    /// Remove a named quest log
+    Remove {
+        /// Log name
+        #[arg(value_parser = database::parse_log_name)]
+        name: String,
+    },
+}
+
+/// Options for adding a quest.
+#[derive(Clone, clap::Args)]
+struct AddArgs {
+    /// Objective
+    objective: String,
+
+    /// Tags, e.g. +backend
+    #[arg(value_name = "+TAG", value_parser = tag::parse_tag)]
+    tags: Vec<String>,
+
+    /// Status
+    #[arg(default_value_t = Status::Pending, long, short, value_enum)]
+    status: Status,
+
+    /// Tier
+    #[arg(default_value_t = Tier::Common, long, short, value_enum)]
+    tier: Tier,
+
+    /// Create a quest chain
+    #[arg(long = "sub", value_name = "QUEST_ID")]
+    chain_id: Option<i64>,
+
+    /// Create a quest chain under the most recently added quest
+    #[arg(conflicts_with = "chain_id", long)]
+    sub_of_last: bool,
+
+    /// Due date, e.g. 2025-03-14, tomorrow, +3d, or fri
+    #[arg(long, value_name = "DATE", value_parser = date::parse)]
+    due: Option<NaiveDate>,
+
+    /// Start date, before which the quest is hidden from the log
+    #[arg(long, value_name = "DATE", value_parser = date::parse)]
+    start: Option<NaiveDate>,
+}
+
+/// Options for showing quests.
+#[derive(Clone, clap::Args)]
+struct LogArgs {
+    /// Only show quests with these statuses [default: pending,ongoing,waiting]
+    #[arg(long, short, value_delimiter = ',', value_enum)]
+    status: Vec<Status>,
+
+    /// Only show quests with these tiers
+    #[arg(long, short, value_delimiter = ',', value_enum)]
+    tier: Vec<Tier>,
+
+    /// Only show quests with any of these tags
+    #[arg(long, value_delimiter = ',', value_parser = tag::parse_name)]
+    tag: Vec<String>,
+
+    /// Only show the quest chain of this quest
+    #[arg(long, value_name = "QUEST_ID")]
+    root: Option<i64>,
+
+    /// Show every quest, including finished quests and quests that have not started
+    #[arg(conflicts_with = "status", long, short)]
+    all: bool,
+
+    /// Sort order of the quests within each chain
+    #[arg(default_value_t = Sort::Id, long, value_enum)]
+    sort: Sort,
+
+    /// Show the archived quest chains instead
+    #[arg(long)]
+    archived: bool,
+
+    /// Never wrap objectives to fit the terminal
+    #[arg(long)]
+    wide: bool,
+}
+
+/// Options for modifying a quest.
+#[derive(Clone, clap::Args)]
+struct ModifyArgs {
+    /// Quest ID
+    quest_id: i64,
+
+    /// Tags to add, e.g. +backend
+    #[arg(value_name = "+TAG", value_parser = tag::parse_tag)]
+    tags: Vec<String>,
+
+    /// Tags to remove
+    #[arg(long, value_delimiter = ',', value_name = "TAG", value_parser = tag::parse_name)]
+    untag: Vec<String>,
+
+    /// Objective
+    #[arg(long, short, value_enum)]
+    objective: Option<String>,
+
+    /// Status
+    #[arg(long, short, value_enum)]
+    status: Option<Status>,
+
+    /// Tier
+    #[arg(long, short, value_enum)]
+    tier: Option<Tier>,
+
+    /// Due date, e.g. 2025-03-14, tomorrow, +3d, or fri
+    #[arg(long, value_name = "DATE", value_parser = date::parse)]
+    due: Option<NaiveDate>,
+
+    /// Remove the due date
+    #[arg(conflicts_with = "due", long)]
+    no_due: bool,
+
+    /// Start date, before which the quest is hidden from the log
+    #[arg(long, value_name = "DATE", value_parser = date::parse)]
+    start: Option<NaiveDate>,
+
+    /// Remove the start date
+    #[arg(conflicts_with = "start", long)]
+    no_start: bool,
 }
 
 /// The CLI interpreter.
@@ -112,194 +449,593 @@ impl Cli {
     const WARNING_COMPLETE_QUEST_CHAIN: &str =
         "Completing a main quest will complete the entire quest chain.";
     const WARNING_DELETE_QUEST: &str =
-        "Deleting a quest will permanently delete the quest and its secondary quests.";
+        "Deleting a quest will move the quest and its secondary quests to the trash.";
+    const WARNING_PURGE_TRASH: &str =
+        "Purging the trash will permanently delete its quests and clear the undo history.";
+    const WARNING_REMOVE_LOG: &str =
+        "Removing a quest log will permanently delete every quest in the log.";
 
-    /// Interprets the parsed arguments from the command line.
-    pub fn interpret(args: Args) {
-        match args.command() {
-            Command::Abandon { quest_id } => {
-                Self::abandon_quest(quest_id);
-            }
-            Command::Accept { quest_id } => {
-                Self::accept_quest(quest_id);
-            }
-            Command::Add {
-                objective,
-                status,
-                tier,
-                chain_id,
-            } => {
-                Self::add_quest(objective, status, tier, chain_id);
-            }
-            Command::Complete { quest_id } => {
-                Self::complete_quest(quest_id);
-            }
-            Command::Delete { quest_id } => {
-                Self::delete_quest(quest_id);
-            }
-            Command::Log => {
-                Self::show_quests();
+    /// Interprets the parsed arguments from the command line, and reports any error to the user.
+    pub fn interpret(args: Args) -> ExitCode {
+        if let Err(err) = args.check_output() {
+            err.exit();
+        }
+
+        match Self::execute(args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                err.exit_code()
             }
-            Command::Modify {
-                quest_id,
-                objective,
-                status,
-                tier,
-            } => {
-                Self::modify_quest(quest_id, objective, status, tier);
+        }
+    }
+
+    /// Executes the parsed command.
+    fn execute(args: Args) -> Result<(), QuestError> {
+        // Logs are created and managed without opening a log.
+        match args.command() {
+            Command::Init => return Self::init_log(),
+            Command::Logs { command } => return Self::manage_logs(command),
+            _ => {}
+        }
+
+        let database = Database::locate(args.db.clone(), args.log.as_deref(), args.global)?;
+        let output = args.output;
+
+        match args.command() {
+            Command::Abandon { quest_id } => Self::abandon_quest(&database, quest_id, output),
+            Command::Accept { quest_id } => Self::accept_quest(&database, quest_id, output),
+            Command::Add(args) => Self::add_quest(&database, args, output),
+            Command::Archive {
+                older_than,
+                auto,
+                no_auto,
+            } => Self::archive_quests(&database, older_than, auto, no_auto),
+            Command::Complete { quest_id } => Self::complete_quest(&database, quest_id, output),
+            Command::Delete { quest_id } => Self::delete_quest(&database, quest_id),
+            Command::Doctor { fix, orphans } => Self::diagnose(&database, fix, orphans),
+            Command::Export { format } => Self::export_quests(&database, format),
+            Command::History { quest_id } => Self::show_history(&database, quest_id),
+            Command::Import {
+                file,
+                format,
+                merge,
+                dry_run,
+            } => Self::import_quests(&database, file, format, merge, dry_run),
+            Command::Log(args) => Self::show_quests(&database, args, output),
+            Command::Init | Command::Logs { .. } => {
+                unreachable!("logs are managed without a database")
             }
+            Command::Modify(args) => Self::modify_quest(&database, args, output),
+            Command::Move { quest_id, to, .. } => Self::move_quest(&database, quest_id, to),
+            Command::Note { quest_id, text } => Self::add_note(&database, quest_id, text),
+            Command::Purge { older_than } => Self::purge_trash(&database, older_than),
+            Command::Redo => Self::redo(&database),
+            Command::Restore { quest_id } => Self::restore_quest(&database, quest_id),
+            Command::Show { quest_id } => Self::show_quest(&database, quest_id, output),
+            Command::Tags => Self::show_tags(&database),
+            Command::Trash => Self::show_trash(&database),
+            Command::Undo => Self::undo(&database),
         }
     }
 
-    /// Warns the user and asks for confirmation before proceeding.
+    /// Warns the user and asks for confirmation before proceeding. The prompt is written to
+    /// standard error, so that it never mixes with JSON output.
     fn confirmation_warning(message: &str) -> bool {
         // Warn the user.
-        println!("Warning: {}", message);
-        print!("Proceed (y/N)? ");
+        eprintln!("Warning: {}", message);
+        eprint!("Proceed (y/N)? ");
 
-        // Get the user input.
+        // Get the user input. Input that cannot be read, such as invalid UTF-8, is not confirmation.
         let mut input = String::new();
-        let _ = stdout().flush();
-        stdin().read_line(&mut input).expect("invalid string");
+        if stdin().read_line(&mut input).is_err() {
+            return false;
+        }
         input = input.trim().to_lowercase();
 
         // Only proceed on "y" or "yes".
         input == "y" || input == "yes"
     }
 
+    /// Prints a value as JSON.
+    fn print_json(value: &impl Serialize) -> Result<(), QuestError> {
+        let mut stdout = stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, value)?;
+        writeln!(stdout)?;
+
+        Ok(())
+    }
+
+    /// Reports the outcome of a command on a quest: the message as text, or the quest as it now
+    /// is as JSON.
+    fn report_quest(
+        quest_dao: &QuestDao,
+        quest_id: i64,
+        output: Output,
+        message: &str,
+    ) -> Result<(), QuestError> {
+        match output {
+            Output::Text => println!("{}", message),
+            Output::Json => Self::print_json(&quest_dao.get_quest(quest_id)?)?,
+        }
+
+        Ok(())
+    }
+
     /// Abandons the specified quest.
-    fn abandon_quest(quest_id: i64) {
+    fn abandon_quest(database: &Database, quest_id: i64, output: Output) -> Result<(), QuestError> {
         // Get the quest from the database.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
         let quest_dao = QuestDao::new(&conn);
-        let quest = quest_dao.get_quest(quest_id);
+        let quest = quest_dao.get_quest(quest_id)?;
 
         // Check if the quest is already abandoned.
         if quest.status() == Status::Abandoned {
-            println!("Quest {} is already abandoned.", quest_id);
-            return;
+            let message = format!("Quest {} is already abandoned.", quest_id);
+            return Self::report_quest(&quest_dao, quest_id, output, &message);
         }
 
         // Always ask for confirmation before accepting a quest chain.
-        if quest_dao.is_main_quest(quest_id)
+        if quest_dao.is_main_quest(quest_id)?
             && !Self::confirmation_warning(Self::WARNING_ABANDON_QUEST_CHAIN)
         {
-            println!("Quest {} not abandoned.", quest_id);
-            return;
+            let message = format!("Quest {} not abandoned.", quest_id);
+            return Self::report_quest(&quest_dao, quest_id, output, &message);
         }
 
-        quest_dao.update_chain_status(quest_id, Status::Abandoned);
-        println!("Quest {} abandoned.", quest_id);
+        let recording = UndoDao::new(&conn).begin(&format!("abandon quest {}", quest_id))?;
+        quest_dao.update_chain_status(quest_id, Status::Abandoned)?;
+        recording.commit()?;
+
+        let message = format!("Quest {} abandoned.", quest_id);
+        Self::report_quest(&quest_dao, quest_id, output, &message)
     }
 
     /// Accepts the specified quest.
-    fn accept_quest(quest_id: i64) {
+    fn accept_quest(database: &Database, quest_id: i64, output: Output) -> Result<(), QuestError> {
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
 
         // Update the quest status to ongoing.
         let quest_dao = QuestDao::new(&conn);
-        let mut quest = quest_dao.get_quest(quest_id);
+        let mut quest = quest_dao.get_quest(quest_id)?;
 
         // Check if the quest is already accepted.
         if quest.status() == Status::Ongoing {
-            println!("Quest is already accepted.");
-            return;
+            return Self::report_quest(&quest_dao, quest_id, output, "Quest is already accepted.");
         }
 
         *quest.status_mut() = Status::Ongoing;
-        quest_dao.update_quest(&quest);
-        println!("Quest {} accepted!", quest_id);
+        let recording = UndoDao::new(&conn).begin(&format!("accept quest {}", quest_id))?;
+        quest_dao.update_quest(&quest)?;
+        recording.commit()?;
+
+        let message = format!("Quest {} accepted!", quest_id);
+        Self::report_quest(&quest_dao, quest_id, output, &message)
+    }
+
+    /// Adds a note to the journal of a quest, written in the text editor if no text is given.
+    fn add_note(
+        database: &Database,
+        quest_id: i64,
+        text: Option<String>,
+    ) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        // Check if the quest exists before opening the editor.
+        let quest_dao = QuestDao::new(&conn);
+        quest_dao.get_quest(quest_id)?;
+
+        let text = match text {
+            Some(text) => text,
+            None => Self::edit_note(quest_id)?,
+        };
+
+        let text = text.trim();
+        if text.is_empty() {
+            println!("Empty note, quest {} not noted.", quest_id);
+            return Ok(());
+        }
+
+        let recording = UndoDao::new(&conn).begin(&format!("note quest {}", quest_id))?;
+        let note_dao = NoteDao::new(&conn);
+        note_dao.add_note(quest_id, text)?;
+        recording.commit()?;
+        println!("Note added to quest {}.", quest_id);
+
+        Ok(())
     }
 
     /// Adds a quest to the log.
-    fn add_quest(objective: String, status: Status, tier: Tier, chain_id: Option<i64>) {
+    fn add_quest(database: &Database, args: AddArgs, output: Output) -> Result<(), QuestError> {
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
 
-        // Construct and save the quest.
-        let quest = Quest::new(objective.trim().to_owned(), status, tier, chain_id);
+        // Find the parent quest if chaining to the last quest.
         let quest_dao = QuestDao::new(&conn);
-        quest_dao.add_quest(&quest);
+        let chain_id = if args.sub_of_last {
+            let last_quest = quest_dao.get_last_quest()?.ok_or(QuestError::EmptyLog)?;
+            Some(last_quest.id())
+        } else {
+            args.chain_id
+        };
+
+        // Construct and save the quest.
+        let objective = args.objective.trim().to_owned();
+        let mut quest = Quest::new(objective, args.status, args.tier, chain_id);
+        *quest.due_mut() = args.due;
+        *quest.start_mut() = args.start;
+        *quest.tags_mut() = args.tags;
+        quest.tags_mut().sort();
+        quest.tags_mut().dedup();
+        let recording = UndoDao::new(&conn).begin(&format!("add quest `{}`", quest.objective()))?;
+        let quest = quest_dao.add_quest(&quest)?;
+        recording.commit()?;
+
+        match output {
+            Output::Text => println!("Quest {} added.", quest.id()),
+            Output::Json => Self::print_json(&quest)?,
+        }
+
+        Ok(())
+    }
+
+    /// Moves finished quest chains into the archive, or sets when they are archived automatically.
+    fn archive_quests(
+        database: &Database,
+        older_than: Option<NaiveDate>,
+        auto: Option<String>,
+        no_auto: bool,
+    ) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+        let archive_dao = ArchiveDao::new(&conn);
+
+        if let Some(age) = auto {
+            archive_dao.set_auto_archive(Some(&age))?;
+            println!(
+                "Quest chains finished {} ago will be archived automatically.",
+                age
+            );
+            return Ok(());
+        }
+
+        if no_auto {
+            archive_dao.set_auto_archive(None)?;
+            println!("Quest chains will no longer be archived automatically.");
+            return Ok(());
+        }
+
+        let archived =
+            archive_dao.archive(&database.archive(), older_than.map(date::start_of_day))?;
+        println!("Archived {} quest chain(s).", archived);
+
+        Ok(())
     }
 
     /// Completes a quest.
-    fn complete_quest(quest_id: i64) {
+    fn complete_quest(
+        database: &Database,
+        quest_id: i64,
+        output: Output,
+    ) -> Result<(), QuestError> {
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
 
         // Update the quest status to completed.
         let quest_dao = QuestDao::new(&conn);
-        let quest = quest_dao.get_quest(quest_id);
+        let quest = quest_dao.get_quest(quest_id)?;
 
         if quest.status() == Status::Completed {
-            println!("Quest is already completed.");
-            return;
+            return Self::report_quest(&quest_dao, quest_id, output, "Quest is already completed.");
         }
 
         // Always ask for confirmation before completing a quest chain.
-        if quest_dao.is_main_quest(quest_id)
+        if quest_dao.is_main_quest(quest_id)?
             && !Self::confirmation_warning(Self::WARNING_COMPLETE_QUEST_CHAIN)
         {
-            println!("Quest {} not completed.", quest_id);
-            return;
+            let message = format!("Quest {} not completed.", quest_id);
+            return Self::report_quest(&quest_dao, quest_id, output, &message);
         }
 
-        quest_dao.update_chain_status(quest_id, Status::Completed);
-        println!("Quest {} completed!", quest_id);
+        let recording = UndoDao::new(&conn).begin(&format!("complete quest {}", quest_id))?;
+        quest_dao.update_chain_status(quest_id, Status::Completed)?;
+        recording.commit()?;
+
+        let message = format!("Quest {} completed!", quest_id);
+        Self::report_quest(&quest_dao, quest_id, output, &message)
     }
 
     /// Deletes a quest, and its secondary quests, from the log.
-    fn delete_quest(quest_id: i64) {
+    fn delete_quest(database: &Database, quest_id: i64) -> Result<(), QuestError> {
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
+
+        // Check if the quest exists before asking for confirmation.
+        let quest_dao = QuestDao::new(&conn);
+        quest_dao.get_quest(quest_id)?;
 
         // Always ask for confirmation before deleting a quest.
         if !Self::confirmation_warning(Self::WARNING_DELETE_QUEST) {
             println!("Quest {} not deleted.", quest_id);
-            return;
+            return Ok(());
         }
 
         // Delete the quest (chain).
-        let quest_dao = QuestDao::new(&conn);
-        quest_dao.delete_chain(quest_id);
-        println!("Quest {} deleted.", quest_id);
+        let recording = UndoDao::new(&conn).begin(&format!("delete quest {}", quest_id))?;
+        quest_dao.delete_chain(quest_id)?;
+        recording.commit()?;
+        println!(
+            "Quest {} moved to the trash. Run `quest restore {}` to bring it back.",
+            quest_id, quest_id
+        );
+
+        Ok(())
+    }
+
+    /// Writes a note in the text editor named by `$VISUAL` or `$EDITOR`, and returns its text.
+    fn edit_note(quest_id: i64) -> Result<String, QuestError> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_owned());
+
+        // The editor may include arguments, such as `code --wait`.
+        let mut words = editor.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| QuestError::EditorFailure("no editor is set".to_owned()))?;
+
+        let path = Self::create_note_file(quest_id)?;
+
+        let status = process::Command::new(program)
+            .args(words)
+            .arg(&path)
+            .status()
+            .map_err(|err| QuestError::EditorFailure(format!("`{}`: {}", editor, err)));
+        let text = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        if !status?.success() {
+            return Err(QuestError::EditorFailure(format!(
+                "`{}` exited with an error",
+                editor
+            )));
+        }
+
+        Ok(text?)
+    }
+
+    /// Creates an empty file for a note in the temporary directory, and returns its path. The file
+    /// gets a random name and must not exist yet, so that a file or symbolic link planted at the
+    /// path by another user is never written through.
+    fn create_note_file(quest_id: i64) -> Result<PathBuf, QuestError> {
+        const ATTEMPTS: usize = 16;
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        for _ in 0..ATTEMPTS {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(process::id());
+            let file_name = format!("quest-{}-note-{:016x}.md", quest_id, hasher.finish());
+
+            let path = env::temp_dir().join(file_name);
+            match options.open(&path) {
+                Ok(_) => return Ok(path),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(QuestError::EditorFailure(
+            "no temporary file could be created for the note".to_owned(),
+        ))
+    }
+
+    /// Checks the quest log for problems, and fixes them if asked to.
+    fn diagnose(database: &Database, fix: bool, orphan_fix: OrphanFix) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let doctor = Doctor::new(&conn);
+        let problems = doctor.diagnose()?;
+
+        if problems.is_empty() {
+            println!("No problems found.");
+            return Ok(());
+        }
+
+        println!("Found {} problem(s):", problems.len());
+        for problem in &problems {
+            println!("  {}", problem);
+        }
+
+        if !fix {
+            if problems.iter().any(|problem| problem.is_fixable()) {
+                println!("Run `quest doctor --fix` to fix them.");
+            }
+            return Ok(());
+        }
+
+        let fixed = doctor.fix(&problems, orphan_fix)?;
+        println!("Fixed {} problem(s).", fixed);
+
+        if fixed < problems.len() {
+            println!("Restore the quest log from a backup to fix the remaining problems.");
+        }
+
+        Ok(())
+    }
+
+    /// Creates a project log in the current directory.
+    fn init_log() -> Result<(), QuestError> {
+        let database = Database::init_project_log(&env::current_dir()?)?;
+        println!("Quest log initialized in {}.", database.path().display());
+
+        Ok(())
+    }
+
+    /// Creates, lists, or removes named quest logs.
+    fn manage_logs(command: LogsCommand) -> Result<(), QuestError> {
+        match command {
+            LogsCommand::Create { name } => {
+                Database::create_log(&name)?;
+                println!("Quest log `{}` created.", name);
+            }
+            LogsCommand::List => {
+                let columns: Vec<Cell> = vec![
+                    Cell::from("Log".underline()),
+                    Cell::from("Path".underline()),
+                ];
+                let mut table = Table::new(columns);
+
+                for name in Database::list_logs()? {
+                    let database = Database::named(&name)?;
+                    let row = vec![
+                        Cell::from(&name),
+                        Cell::from(database.path().display().to_string()),
+                    ];
+                    table.add(row);
+                }
+
+                table.show();
+            }
+            LogsCommand::Remove { name } => {
+                // Check if the log exists and may be removed before asking for confirmation.
+                Database::removable_log(&name)?;
+
+                if !Self::confirmation_warning(Self::WARNING_REMOVE_LOG) {
+                    println!("Quest log `{}` not removed.", name);
+                    return Ok(());
+                }
+
+                Database::remove_log(&name)?;
+                println!("Quest log `{}` removed.", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every quest in the log to standard output in an interchange format.
+    fn export_quests(database: &Database, format: Format) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let exporter = Exporter::new(&conn);
+        exporter.export(format, stdout().lock())?;
+
+        Ok(())
+    }
+
+    /// Imports quests from a file, or from standard input.
+    fn import_quests(
+        database: &Database,
+        file: PathBuf,
+        format: Option<Format>,
+        merge: bool,
+        dry_run: bool,
+    ) -> Result<(), QuestError> {
+        let format = format
+            .or_else(|| Format::from_path(&file))
+            .unwrap_or(Format::Json);
+
+        // Read the whole file before touching the log.
+        let (quests, unmapped) = if file.as_os_str() == "-" {
+            Importer::read(format, stdin().lock())?
+        } else {
+            let reader = fs::File::open(&file).map_err(|err| {
+                QuestError::InvalidImport(format!("cannot read `{}`: {}", file.display(), err))
+            })?;
+            Importer::read(format, reader)?
+        };
+
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let recording = UndoDao::new(&conn).begin(&format!("import {}", file.display()))?;
+        let report = Importer::new(&conn).import(&quests, merge, dry_run)?;
+
+        // Report what was left behind, before the outcome.
+        if !unmapped.is_empty() {
+            println!("Could not map:");
+            for item in &unmapped {
+                println!("  {}", item);
+            }
+        }
+
+        if dry_run {
+            println!(
+                "Would import {} quest(s), and merge {} quest(s) already in the log.",
+                report.imported(),
+                report.merged()
+            );
+            return Ok(());
+        }
+
+        recording.commit()?;
+        println!(
+            "Imported {} quest(s), and merged {} quest(s) already in the log.",
+            report.imported(),
+            report.merged()
+        );
+
+        Ok(())
     }
 
     /// Modifies a quest from the log.
     fn modify_quest(
-        quest_id: i64,
-        objective: Option<String>,
-        status: Option<Status>,
-        tier: Option<Tier>,
-    ) {
+        database: &Database,
+        args: ModifyArgs,
+        output: Output,
+    ) -> Result<(), QuestError> {
+        let quest_id = args.quest_id;
+
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
 
         // Update the modified fields.
         let quest_dao = QuestDao::new(&conn);
-        let mut quest = quest_dao.get_quest(quest_id);
+        let mut quest = quest_dao.get_quest(quest_id)?;
 
-        if let Some(objective) = objective {
+        if let Some(objective) = args.objective {
             *quest.objective_mut() = objective;
         }
 
-        if let Some(status) = status {
+        if let Some(status) = args.status {
             *quest.status_mut() = status;
         }
 
-        if let Some(tier) = tier {
+        if let Some(tier) = args.tier {
             *quest.tier_mut() = tier;
         }
 
-        quest_dao.update_quest(&quest);
-        println!("Quest {} modified.", quest_id);
+        if args.no_due {
+            *quest.due_mut() = None;
+        } else if let Some(due) = args.due {
+            *quest.due_mut() = Some(due);
+        }
+
+        if args.no_start {
+            *quest.start_mut() = None;
+        } else if let Some(start) = args.start {
+            *quest.start_mut() = Some(start);
+        }
+
+        quest.tags_mut().extend(args.tags);
+        quest.tags_mut().retain(|tag| !args.untag.contains(tag));
+        quest.tags_mut().sort();
+        quest.tags_mut().dedup();
+
+        let recording = UndoDao::new(&conn).begin(&format!("modify quest {}", quest_id))?;
+        quest_dao.update_quest(&quest)?;
+        recording.commit()?;
+
+        let message = format!("Quest {} modified.", quest_id);
+        Self::report_quest(&quest_dao, quest_id, output, &message)
     }
 
     /// Populates the table with quest chains, where each secondary quest chain
@@ -314,6 +1050,9 @@ impl Cli {
         // Prepended to the quest objective. Necessary to show the chain connections and depth.
         let mut prefix = String::new();
 
+        // Prepended to the wrapped lines of the objective instead, continuing the connections.
+        let mut continuation = String::new();
+
         // Check if quest chains are nested
         for &is_nested in &is_depth_nested[..depth] {
             if is_nested {
@@ -323,20 +1062,19 @@ impl Cli {
             }
         }
 
+        continuation.push_str(&prefix);
+
         if is_terminal {
             // Close the nested list of chains.
             prefix.push_str("└── ");
+            continuation.push_str("    ");
         } else {
             prefix.push_str("├── ");
+            continuation.push_str("│   ");
         }
 
-        let row = vec![
-            Cell::from(chain.id()),
-            Cell::from(prefix + chain.objective()),
-            Cell::from(chain.status()),
-            Cell::from(chain.tier()),
-        ];
-        table.add(row);
+        let objective = Cell::from(chain.objective()).with_prefix(prefix, continuation);
+        table.add(Self::chain_row(chain, objective));
 
         if depth < is_depth_nested.len() {
             is_depth_nested[depth] = !is_terminal; // Keep │ if it's not the last item
@@ -362,15 +1100,304 @@ impl Cli {
         }
     }
 
-    /// Shows all quests in the log.
-    fn show_quests() {
+    /// Constructs a table row for the main quest of a chain.
+    fn chain_row(chain: &Chain, objective: Cell) -> Vec<Cell> {
+        // Highlight the due date if the quest is overdue.
+        let due = match chain.due() {
+            Some(due) if chain.is_overdue(date::today()) => {
+                due.format(DATE_FORMAT).to_string().red().bold()
+            }
+            Some(due) => due.format(DATE_FORMAT).to_string().into(),
+            None => "".into(),
+        };
+
+        let tags: Vec<String> = chain.tags().iter().map(|tag| format!("+{}", tag)).collect();
+
+        vec![
+            Cell::from(chain.id()),
+            objective,
+            Cell::from(chain.status()),
+            Cell::from(chain.tier()),
+            Cell::from(due),
+            Cell::from(tags.join(" ")),
+        ]
+    }
+
+    /// Moves a quest, and its secondary quests, under another quest or to the top of the log.
+    fn move_quest(
+        database: &Database,
+        quest_id: i64,
+        chain_id: Option<i64>,
+    ) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let recording = UndoDao::new(&conn).begin(&format!("move quest {}", quest_id))?;
+        let quest_dao = QuestDao::new(&conn);
+        quest_dao.move_quest(quest_id, chain_id)?;
+        recording.commit()?;
+
+        match chain_id {
+            Some(chain_id) => println!("Quest {} moved under quest {}.", quest_id, chain_id),
+            None => println!("Quest {} is now a main quest.", quest_id),
+        }
+
+        Ok(())
+    }
+
+    /// Permanently deletes the quests in the trash, or only those deleted before a date.
+    fn purge_trash(database: &Database, older_than: Option<NaiveDate>) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        // Always ask for confirmation before purging the trash.
+        if !Self::confirmation_warning(Self::WARNING_PURGE_TRASH) {
+            println!("Trash not purged.");
+            return Ok(());
+        }
+
+        let quest_dao = QuestDao::new(&conn);
+        let purged = quest_dao.purge(older_than.map(date::start_of_day))?;
+        println!("Purged {} quest(s).", purged);
+
+        Ok(())
+    }
+
+    /// Redoes the last undone command.
+    fn redo(database: &Database) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let undo_dao = UndoDao::new(&conn);
+        match undo_dao.redo()? {
+            Some(operation) => println!(
+                "Redid `{}` from {}.",
+                operation.description(),
+                date::format_datetime(operation.created_at())
+            ),
+            None => println!("Nothing to redo."),
+        }
+
+        Ok(())
+    }
+
+    /// Restores a quest, and the secondary quests deleted with it, from the trash.
+    fn restore_quest(database: &Database, quest_id: i64) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let recording = UndoDao::new(&conn).begin(&format!("restore quest {}", quest_id))?;
+        let quest_dao = QuestDao::new(&conn);
+        quest_dao.restore_chain(quest_id)?;
+        recording.commit()?;
+        println!("Quest {} restored.", quest_id);
+
+        Ok(())
+    }
+
+    /// Shows the fields, chain ancestry, secondary quests, and journal of a quest.
+    fn show_quest(database: &Database, quest_id: i64, output: Output) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        // Get the quest, its chain, and its notes.
+        let quest_dao = QuestDao::new(&conn);
+        let quest = quest_dao.get_quest(quest_id)?;
+        let ancestors = quest_dao.get_ancestors(quest_id)?;
+        let secondary_quests = quest_dao.get_secondary_quests(quest_id)?;
+        let chain_counts = quest_dao.get_chain_counts(quest_id)?;
+        let note_dao = NoteDao::new(&conn);
+        let notes = note_dao.get_notes(quest_id)?;
+
+        if output == Output::Json {
+            return Self::print_json(&QuestDetails {
+                quest: &quest,
+                ancestors: &ancestors,
+                quests: &secondary_quests,
+                subtree: chain_counts.into_iter().collect(),
+                notes: &notes,
+            });
+        }
+
+        let format_date = |date: Option<NaiveDate>| match date {
+            Some(date) => date.format(DATE_FORMAT).to_string(),
+            None => "None".to_owned(),
+        };
+        let format_time = |time: Option<DateTime<Utc>>| match time {
+            Some(time) => date::format_datetime(time),
+            None => "Unknown".to_owned(),
+        };
+
+        // Highlight the due date if the quest is overdue.
+        let mut due = format_date(quest.due()).normal();
+        if quest.is_overdue(date::today()) {
+            due = format!("{} (overdue)", due).red().bold();
+        }
+
+        let mut tags: Vec<String> = quest.tags().iter().map(|tag| format!("+{}", tag)).collect();
+        if tags.is_empty() {
+            tags.push("None".to_owned());
+        }
+
+        println!(
+            "{}",
+            format!("Quest {}: {}", quest_id, quest.objective()).bold()
+        );
+        println!("Status:  {}", quest.status());
+        println!("Tier:    {}", quest.tier().to_colored_string());
+        println!("Due:     {}", due);
+        println!("Start:   {}", format_date(quest.start()));
+        println!("Tags:    {}", tags.join(" "));
+        println!("Created: {}", format_time(quest.created_at()));
+        println!("Updated: {}", format_time(quest.updated_at()));
+
+        // Show the path from the main quest down to this quest.
+        if !ancestors.is_empty() {
+            let mut path: Vec<String> = ancestors
+                .iter()
+                .map(|ancestor| format!("{} {}", ancestor.id(), ancestor.objective()))
+                .collect();
+            path.push(format!("{} {}", quest_id, quest.objective()));
+            println!("Chain:   {}", path.join(" › "));
+        }
+
+        // Show the roll-up of every secondary quest, direct or nested, and the direct ones.
+        if !secondary_quests.is_empty() {
+            let total: i64 = chain_counts.iter().map(|(_, count)| count).sum();
+            let counts: Vec<String> = chain_counts
+                .iter()
+                .map(|(status, count)| format!("{} {}", count, status))
+                .collect();
+            let noun = if total == 1 { "quest" } else { "quests" };
+            println!(
+                "Subtree: {} secondary {} ({})",
+                total,
+                noun,
+                counts.join(", ")
+            );
+
+            let columns: Vec<Cell> = vec![
+                Cell::from("ID".underline()),
+                Cell::from("Secondary Quest".underline()),
+                Cell::from("Status".underline()),
+                Cell::from("Tier".underline()),
+            ];
+            let mut table = Table::new(columns);
+
+            for secondary_quest in &secondary_quests {
+                let row = vec![
+                    Cell::from(secondary_quest.id()),
+                    Cell::from(secondary_quest.objective()),
+                    Cell::from(secondary_quest.status()),
+                    Cell::from(secondary_quest.tier()),
+                ];
+                table.add(row);
+            }
+
+            println!();
+            table.show();
+        }
+
+        // Show the journal.
+        println!();
+        if notes.is_empty() {
+            println!("No notes.");
+        }
+
+        for note in notes {
+            println!("{}", date::format_datetime(note.created_at()).underline());
+            for line in note.text().lines() {
+                println!("  {}", line);
+            }
+            println!();
+        }
+
+        Ok(())
+    }
+
+    /// Shows the creation time, last modification time, and status history of a quest.
+    fn show_history(database: &Database, quest_id: i64) -> Result<(), QuestError> {
         // Open the database connection.
-        let database = Database::new();
-        let conn = database.conn();
+        let conn = database.conn()?;
 
-        // Get all quest chains from the log.
+        // Get the quest and its status transitions.
         let quest_dao = QuestDao::new(&conn);
-        let chains = quest_dao.get_all_chains();
+        let quest = quest_dao.get_quest(quest_id)?;
+        let history = quest_dao.get_history(quest_id)?;
+
+        // Quests created before timestamps were recorded have no known times.
+        let format_time = |time: Option<DateTime<Utc>>| match time {
+            Some(time) => date::format_datetime(time),
+            None => "Unknown".to_owned(),
+        };
+
+        println!("Quest {}: {}", quest_id, quest.objective());
+        println!("Created: {}", format_time(quest.created_at()));
+        println!("Updated: {}", format_time(quest.updated_at()));
+
+        if history.is_empty() {
+            println!("No status changes recorded.");
+            return Ok(());
+        }
+
+        // Populate and show the timeline.
+        let columns: Vec<Cell> = vec![
+            Cell::from("Time".underline()),
+            Cell::from("Status".underline()),
+        ];
+        let mut table = Table::new(columns);
+
+        for transition in history {
+            let row = vec![
+                Cell::from(date::format_datetime(transition.changed_at())),
+                Cell::from(transition.status()),
+            ];
+            table.add(row);
+        }
+
+        println!();
+        table.show();
+
+        Ok(())
+    }
+
+    /// Shows all quests in the log.
+    fn show_quests(database: &Database, args: LogArgs, output: Output) -> Result<(), QuestError> {
+        // Archive finished chains first if asked to, so the log only shows active quests.
+        let conn = database.conn()?;
+        let archive_dao = ArchiveDao::new(&conn);
+        if let Some(age) = archive_dao.get_auto_archive()? {
+            let cutoff = date::parse_age(&age).map_err(QuestError::StorageFailure)?;
+            archive_dao.archive(&database.archive(), Some(date::start_of_day(cutoff)))?;
+        }
+
+        // Open the archive instead if asked to.
+        let archive = database.archive();
+        let conn = if args.archived { archive.conn()? } else { conn };
+
+        // Construct the filter, hiding finished quests unless asked for.
+        let mut filter = QuestFilter::new(date::today());
+        *filter.include_deferred_mut() = args.all;
+        *filter.root_mut() = args.root;
+        *filter.sort_mut() = args.sort;
+        *filter.tags_mut() = args.tag;
+        *filter.tiers_mut() = args.tier;
+        *filter.statuses_mut() = if args.all || !args.status.is_empty() {
+            args.status
+        } else {
+            vec![Status::Pending, Status::Ongoing, Status::Waiting]
+        };
+
+        // Get the matching quest chains from the log.
+        let quest_dao = QuestDao::new(&conn);
+        if let Some(root) = filter.root() {
+            quest_dao.get_quest(root)?;
+        }
+        let chains = quest_dao.get_chains(&filter)?;
+
+        if output == Output::Json {
+            return Self::print_json(&chains);
+        }
 
         // Populate and show the table.
         let columns: Vec<Cell> = vec![
@@ -378,17 +1405,23 @@ impl Cli {
             Cell::from("Objective".underline()),
             Cell::from("Status".underline()),
             Cell::from("Tier".underline()),
+            Cell::from("Due".underline()),
+            Cell::from("Tags".underline()),
         ];
         let mut table = Table::new(columns);
 
+        // Wrap the objectives to fit the terminal, unless asked not to.
+        table.wrap(
+            1,
+            if args.wide {
+                None
+            } else {
+                table::terminal_width()
+            },
+        );
+
         for chain in chains {
-            let row = vec![
-                Cell::from(chain.id()),
-                Cell::from(chain.objective()),
-                Cell::from(chain.status()),
-                Cell::from(chain.tier()),
-            ];
-            table.add(row);
+            table.add(Self::chain_row(&chain, Cell::from(chain.objective())));
 
             for (chain_idx, child_chain) in chain.chains().iter().enumerate() {
                 Self::populate_table(
@@ -402,5 +1435,91 @@ impl Cli {
         }
 
         table.show();
+
+        Ok(())
+    }
+
+    /// Shows the quest chains in the trash.
+    fn show_trash(database: &Database) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let quest_dao = QuestDao::new(&conn);
+        let trash = quest_dao.get_trash()?;
+
+        // Populate and show the table.
+        let columns: Vec<Cell> = vec![
+            Cell::from("ID".underline()),
+            Cell::from("Objective".underline()),
+            Cell::from("Status".underline()),
+            Cell::from("Quests".underline()),
+            Cell::from("Deleted".underline()),
+        ];
+        let mut table = Table::new(columns);
+
+        for (quest, count) in trash {
+            let deleted_at = quest.deleted_at().map(date::format_datetime);
+            let row = vec![
+                Cell::from(quest.id()),
+                Cell::from(quest.objective()),
+                Cell::from(quest.status()),
+                Cell::from(count),
+                Cell::from(deleted_at.unwrap_or_default()),
+            ];
+            table.add(row);
+        }
+
+        table.show();
+
+        Ok(())
+    }
+
+    /// Undoes the last command that changed the quest log.
+    fn undo(database: &Database) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        let undo_dao = UndoDao::new(&conn);
+        match undo_dao.undo()? {
+            Some(operation) => println!(
+                "Undid `{}` from {}.",
+                operation.description(),
+                date::format_datetime(operation.created_at())
+            ),
+            None => println!("Nothing to undo."),
+        }
+
+        Ok(())
+    }
+
+    /// Shows all tags, and the number of open and closed quests labelled with each.
+    fn show_tags(database: &Database) -> Result<(), QuestError> {
+        // Open the database connection.
+        let conn = database.conn()?;
+
+        // Get all tags from the log.
+        let tag_dao = TagDao::new(&conn);
+        let tags = tag_dao.get_all_tags()?;
+
+        // Populate and show the table.
+        let columns: Vec<Cell> = vec![
+            Cell::from("Tag".underline()),
+            Cell::from("Open".underline()),
+            Cell::from("Closed".underline()),
+        ];
+        let mut table = Table::new(columns);
+
+        for tag in tags {
+            let row = vec![
+                Cell::from(format!("+{}", tag.name())),
+                Cell::from(tag.open()),
+                Cell::from(tag.closed()),
+            ];
+            table.add(row);
+        }
+
+        table.show();
+
+        Ok(())
     }
 }
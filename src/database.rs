@@ -1,11 +1,15 @@
 use std::{
-    fs,
+    env, fs,
+    ops::Deref,
     path::{Path, PathBuf},
 };
 
 use directories::ProjectDirs;
 use rusqlite::Connection;
 
+use crate::error::QuestError;
+use crate::migration;
+
 /// Represents a local database reference for storing projects, tasks, and more.
 pub struct Database {
     database_path: PathBuf,
@@ -15,34 +19,284 @@ pub struct Database {
 impl Database {
     const DATABASE_NAME: &str = "questdb.sqlite";
 
-    /// Constructs a new database reference.
-    pub fn new() -> Self {
-        // Read the local data path.
-        let project_dirs = ProjectDirs::from("com", "Ode", "quest").unwrap();
-        let data_dir = project_dirs.data_dir();
+    /// The name of the log stored at the default location.
+    pub const DEFAULT_LOG: &str = "default";
+
+    /// The environment variable naming the database path, used when no location is given.
+    pub const ENV_DATABASE: &str = "QUEST_DB";
+
+    /// The directory holding named logs, inside the data directory.
+    const LOGS_DIR: &str = "logs";
+
+    /// The directory holding a project log, inside the project directory.
+    const PROJECT_DIR: &str = ".quest";
+
+    /// Constructs a new reference to the project log of the current directory or any of its
+    /// parents, falling back to the user-wide database if there is none.
+    pub fn new() -> Result<Self, QuestError> {
+        match Self::find_project_log()? {
+            Some(database_path) => Ok(Self { database_path }),
+            None => Self::global(),
+        }
+    }
+
+    /// Constructs a new reference to the user-wide database.
+    pub fn global() -> Result<Self, QuestError> {
+        let data_dir = Self::data_dir()?;
 
         // Create the data directory if it does not exist.
-        Self::create_dir(data_dir);
+        Self::create_dir(&data_dir)?;
 
         // Construct the database path.
         let database_path = data_dir.join(Self::DATABASE_NAME);
 
-        Self { database_path }
+        Ok(Self { database_path })
+    }
+
+    /// Constructs a reference to the database at the specified path, creating its directory if
+    /// necessary.
+    pub fn at(database_path: PathBuf) -> Result<Self, QuestError> {
+        if let Some(dir) = database_path.parent() {
+            Self::create_dir(dir)?;
+        }
+
+        Ok(Self { database_path })
+    }
+
+    /// Constructs a reference to an existing named log.
+    pub fn named(name: &str) -> Result<Self, QuestError> {
+        if name == Self::DEFAULT_LOG {
+            return Self::global();
+        }
+
+        Self::removable_log(name)
+    }
+
+    /// Constructs a reference to an existing named log that may be removed, which excludes the
+    /// default log.
+    pub fn removable_log(name: &str) -> Result<Self, QuestError> {
+        let database_path = Self::log_path(name)?;
+        if !fs::exists(&database_path)? {
+            return Err(QuestError::UnknownLog(name.to_owned()));
+        }
+
+        Ok(Self { database_path })
+    }
+
+    /// Locates the database to use. An explicit path wins over a named log, which wins over the
+    /// user-wide database if forced, which wins over the `QUEST_DB` environment variable, which
+    /// wins over the project log and then the user-wide database.
+    pub fn locate(
+        database_path: Option<PathBuf>,
+        log: Option<&str>,
+        global: bool,
+    ) -> Result<Self, QuestError> {
+        if let Some(database_path) = database_path {
+            return Self::at(database_path);
+        }
+
+        if let Some(log) = log {
+            return Self::named(log);
+        }
+
+        if global {
+            return Self::global();
+        }
+
+        match env::var_os(Self::ENV_DATABASE) {
+            Some(database_path) if !database_path.is_empty() => Self::at(database_path.into()),
+            _ => Self::new(),
+        }
+    }
+
+    /// Constructs a reference to the archive of this database, which is stored next to it.
+    pub fn archive(&self) -> Self {
+        let stem = self.database_path.file_stem().unwrap_or_default();
+        let mut file_name = stem.to_os_string();
+        file_name.push(".archive.sqlite");
+
+        Self {
+            database_path: self.database_path.with_file_name(file_name),
+        }
+    }
+
+    /// Borrows the path of the database file.
+    pub fn path(&self) -> &Path {
+        &self.database_path
+    }
+
+    /// Opens and borrows the database connection, upgrading the schema if necessary. Foreign keys
+    /// are enforced, since SQLite leaves them off by default.
+    pub fn conn(&self) -> Result<Connection, QuestError> {
+        let mut conn = Connection::open(&self.database_path)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migration::migrate(&mut conn)?;
+        Ok(conn)
+    }
+
+    /// Creates a named log, and returns a reference to it. Does nothing if the log already exists.
+    pub fn create_log(name: &str) -> Result<Self, QuestError> {
+        let logs_dir = Self::data_dir()?.join(Self::LOGS_DIR);
+        Self::create_dir(&logs_dir)?;
+
+        let database = Self {
+            database_path: Self::log_path(name)?,
+        };
+
+        // Opening the connection creates the file and its schema.
+        database.conn()?;
+
+        Ok(database)
+    }
+
+    /// Creates a project log in the specified directory, and returns a reference to it. Does
+    /// nothing if the log already exists.
+    pub fn init_project_log(dir: &Path) -> Result<Self, QuestError> {
+        let database = Self::at(dir.join(Self::PROJECT_DIR).join(Self::DATABASE_NAME))?;
+
+        // Opening the connection creates the file and its schema.
+        database.conn()?;
+
+        Ok(database)
+    }
+
+    /// Gets the names of the default log and every named log, sorted by name.
+    pub fn list_logs() -> Result<Vec<String>, QuestError> {
+        let mut names = vec![Self::DEFAULT_LOG.to_owned()];
+
+        let logs_dir = Self::data_dir()?.join(Self::LOGS_DIR);
+        if fs::exists(&logs_dir)? {
+            for entry in fs::read_dir(&logs_dir)? {
+                let path = entry?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "sqlite")
+                {
+                    // Skip archives, whose names are not valid log names.
+                    if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                        if parse_log_name(name).is_ok() {
+                            names.push(name.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+
+        names[1..].sort();
+
+        Ok(names)
+    }
+
+    /// Permanently deletes a named log and its archive. The default log is never deleted.
+    pub fn remove_log(name: &str) -> Result<(), QuestError> {
+        let database = Self::removable_log(name)?;
+        fs::remove_file(&database.database_path)?;
+
+        let archive = database.archive();
+        if fs::exists(&archive.database_path)? {
+            fs::remove_file(&archive.database_path)?;
+        }
+
+        Ok(())
     }
 
-    /// Opens and borrows the database connection.
-    pub fn conn(&self) -> Connection {
-        Connection::open(&self.database_path).expect("failed to open database connection")
+    /// Gets the path of a named log. The default log has no such path.
+    fn log_path(name: &str) -> Result<PathBuf, QuestError> {
+        if name == Self::DEFAULT_LOG {
+            return Err(QuestError::StorageFailure(format!(
+                "the `{}` log cannot be created or removed",
+                name
+            )));
+        }
+
+        let file_name = format!("{}.sqlite", name);
+        Ok(Self::data_dir()?.join(Self::LOGS_DIR).join(file_name))
     }
 
-    /// Creates a new directory. Panics if an error is encountered.
-    fn create_dir(dir: &Path) {
-        // Create the data directory if necessary.
-        if !fs::exists(&dir)
-            .unwrap_or_else(|_| panic!("failed to check if path `{}` exists", dir.display()))
-        {
-            fs::create_dir_all(&dir)
-                .unwrap_or_else(|_| panic!("failed to create directory: `{}`", dir.display()));
+    /// Finds the project log in the current directory or the nearest parent holding one.
+    fn find_project_log() -> Result<Option<PathBuf>, QuestError> {
+        let current_dir = env::current_dir()?;
+
+        for dir in current_dir.ancestors() {
+            let database_path = dir.join(Self::PROJECT_DIR).join(Self::DATABASE_NAME);
+            if database_path.is_file() {
+                return Ok(Some(database_path));
+            }
         }
+
+        Ok(None)
+    }
+
+    /// Gets the local data directory.
+    fn data_dir() -> Result<PathBuf, QuestError> {
+        let project_dirs = ProjectDirs::from("com", "Ode", "quest").ok_or_else(|| {
+            QuestError::StorageFailure("no home directory could be found".to_owned())
+        })?;
+
+        Ok(project_dirs.data_dir().to_path_buf())
+    }
+
+    /// Creates a new directory if it does not exist.
+    fn create_dir(dir: &Path) -> Result<(), QuestError> {
+        if !fs::exists(dir)? {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A transaction that joins the enclosing transaction, if there is one, so that the writes of a
+/// whole command are committed, or rolled back, together.
+pub struct Transaction<'a> {
+    conn: &'a Connection,
+    tx: Option<rusqlite::Transaction<'a>>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Begins a new transaction, or joins the enclosing one.
+    pub fn new(conn: &'a Connection) -> Result<Self, QuestError> {
+        let tx = if conn.is_autocommit() {
+            Some(conn.unchecked_transaction()?)
+        } else {
+            None
+        };
+
+        Ok(Self { conn, tx })
+    }
+
+    /// Commits the transaction. A joined transaction is committed by its owner instead.
+    pub fn commit(self) -> Result<(), QuestError> {
+        if let Some(tx) = self.tx {
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+    }
+}
+
+/// Parses a log name. Names may only contain letters, digits, dashes, and underscores, so that
+/// each maps to a file in the data directory.
+pub fn parse_log_name(input: &str) -> Result<String, String> {
+    let is_valid = !input.is_empty()
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if is_valid {
+        Ok(input.to_owned())
+    } else {
+        Err(format!(
+            "invalid log name `{}`; use letters, digits, dashes, and underscores",
+            input
+        ))
     }
 }
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveTime, Utc, Weekday};
+
+/// The date format used to parse and show absolute dates.
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The format used to show timestamps in local time.
+pub const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Formats a timestamp in local time.
+pub fn format_datetime(datetime: DateTime<Utc>) -> String {
+    datetime
+        .with_timezone(&Local)
+        .format(DATETIME_FORMAT)
+        .to_string()
+}
+
+/// Gets the time the specified local date starts.
+pub fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let start = date.and_time(NaiveTime::MIN);
+    match start.and_local_timezone(Local).earliest() {
+        Some(start) => start.to_utc(),
+        None => start.and_utc(),
+    }
+}
+
+/// Gets the local date of a timestamp.
+pub fn local_date(datetime: DateTime<Utc>) -> NaiveDate {
+    datetime.with_timezone(&Local).date_naive()
+}
+
+/// Gets the current local date.
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Parses a command line date relative to the current local date. See `parse_relative_to` for the
+/// accepted forms.
+pub fn parse(input: &str) -> Result<NaiveDate, String> {
+    parse_relative_to(input, today())
+}
+
+/// Parses an age such as `30d`, `2w`, or `1m`, and returns the date that long before the current
+/// local date.
+pub fn parse_age(input: &str) -> Result<NaiveDate, String> {
+    let input = input.trim().to_lowercase();
+    shift(today(), &input, true)
+        .ok_or_else(|| format!("invalid age `{}`; expected 30d, 2w, or 1m", input))
+}
+
+/// Checks that an age such as `30d` is valid, and returns it unchanged so it can be stored.
+pub fn validate_age(input: &str) -> Result<String, String> {
+    parse_age(input).map(|_| input.trim().to_lowercase())
+}
+
+/// Parses an absolute or relative date. Accepts absolute dates (`2025-03-14`), named days
+/// (`today`, `tomorrow`, `yesterday`), offsets in days, weeks, or months (`+3d`, `-1w`, `+2m`),
+/// and weekdays (`fri`, `friday`), which resolve to the next such day after `today`.
+pub fn parse_relative_to(input: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let input = input.trim().to_lowercase();
+
+    if let Ok(date) = NaiveDate::parse_from_str(&input, DATE_FORMAT) {
+        return Ok(date);
+    }
+
+    let date = match input.as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.checked_add_days(Days::new(1)),
+        "yesterday" => today.checked_sub_days(Days::new(1)),
+        _ => {
+            if let Some(offset) = input.strip_prefix(['+', '-']) {
+                shift(today, offset, input.starts_with('-'))
+            } else {
+                input.parse::<Weekday>().ok().map(|weekday| {
+                    let days = (weekday.days_since(today.weekday()) + 6) % 7 + 1;
+                    today + Days::new(days.into())
+                })
+            }
+        }
+    };
+
+    date.ok_or_else(|| {
+        format!(
+            "invalid date `{}`; expected YYYY-MM-DD, today, tomorrow, +3d, +2w, +1m, or a weekday",
+            input
+        )
+    })
+}
+
+/// Shifts a date by an offset such as `3d`, `2w`, or `1m`. Returns `None` if the offset is invalid
+/// or the resulting date is out of range.
+fn shift(date: NaiveDate, offset: &str, backwards: bool) -> Option<NaiveDate> {
+    let unit = offset.chars().last()?;
+    let amount = &offset[..offset.len() - unit.len_utf8()];
+
+    // Integer parsing allows a sign, which the offset already had.
+    if !amount.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u32 = amount.parse().ok()?;
+
+    match (unit, backwards) {
+        ('d', false) => date.checked_add_days(Days::new(amount.into())),
+        ('d', true) => date.checked_sub_days(Days::new(amount.into())),
+        ('w', false) => date.checked_add_days(Days::new(u64::from(amount) * 7)),
+        ('w', true) => date.checked_sub_days(Days::new(u64::from(amount) * 7)),
+        ('m', false) => date.checked_add_months(Months::new(amount)),
+        ('m', true) => date.checked_sub_months(Months::new(amount)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Friday.
+    fn friday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, 14).unwrap()
+    }
+
+    fn parse(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+        parse_relative_to(input, today).ok()
+    }
+
+    fn ymd(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn parses_absolute_and_named_dates() {
+        assert_eq!(parse("2024-02-29", friday()), ymd(2024, 2, 29));
+        assert_eq!(parse(" Today ", friday()), ymd(2025, 3, 14));
+        assert_eq!(parse("tomorrow", friday()), ymd(2025, 3, 15));
+        assert_eq!(parse("yesterday", friday()), ymd(2025, 3, 13));
+    }
+
+    #[test]
+    fn parses_offsets() {
+        assert_eq!(parse("+3d", friday()), ymd(2025, 3, 17));
+        assert_eq!(parse("-1w", friday()), ymd(2025, 3, 7));
+        assert_eq!(parse("+0d", friday()), ymd(2025, 3, 14));
+        assert_eq!(parse("+2m", friday()), ymd(2025, 5, 14));
+    }
+
+    #[test]
+    fn clamps_month_offsets_to_the_end_of_the_month() {
+        let end_of_january = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        assert_eq!(parse("+1m", end_of_january), ymd(2024, 2, 29));
+        assert_eq!(parse("+13m", end_of_january), ymd(2025, 2, 28));
+        assert_eq!(parse("-2m", end_of_january), ymd(2023, 11, 30));
+    }
+
+    #[test]
+    fn resolves_weekdays_to_the_next_such_day() {
+        assert_eq!(parse("sat", friday()), ymd(2025, 3, 15));
+        assert_eq!(parse("Thursday", friday()), ymd(2025, 3, 20));
+        // The same weekday means next week, never today.
+        assert_eq!(parse("fri", friday()), ymd(2025, 3, 21));
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for input in [
+            "",
+            "+d",
+            "+3x",
+            "3d",
+            "2025-02-30",
+            "someday",
+            "+-3d",
+            "++3d",
+            "+3dd",
+        ] {
+            assert!(parse(input, friday()).is_none(), "`{}` parsed", input);
+        }
+        assert!(parse("+1d", NaiveDate::MAX).is_none());
+    }
+
+    #[test]
+    fn parses_ages_as_past_dates() {
+        assert!(parse_age("30d").is_ok_and(|date| date < today()));
+        assert_eq!(validate_age(" 2W "), Ok("2w".to_owned()));
+        assert!(validate_age("+2w").is_err());
+    }
+}
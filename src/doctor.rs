@@ -0,0 +1,293 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+use clap::ValueEnum;
+use rusqlite::{params, Connection};
+
+use crate::database::Transaction;
+use crate::error::QuestError;
+use crate::quest::{QuestDao, Status, Tier};
+
+/// A problem found in the quest log.
+#[derive(Clone, Debug)]
+pub enum Problem {
+    /// The parent quest of a quest does not exist.
+    Orphan { quest_id: i64, chain_id: i64 },
+    /// Quests whose parents form a cycle, starting with the oldest quest.
+    Cycle { quest_ids: Vec<i64> },
+    /// A quest has an unknown status.
+    UnknownStatus { quest_id: i64, value: i64 },
+    /// A quest has an unknown tier.
+    UnknownTier { quest_id: i64, value: i64 },
+    /// SQLite reported a problem with the database file itself.
+    Integrity(String),
+}
+
+impl Problem {
+    /// Checks if the problem can be fixed by the doctor.
+    pub fn is_fixable(&self) -> bool {
+        !matches!(self, Self::Integrity(_))
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Orphan { quest_id, chain_id } => write!(
+                formatter,
+                "Quest {} belongs to quest {}, which does not exist.",
+                quest_id, chain_id
+            ),
+            Self::Cycle { quest_ids } => {
+                let mut path: Vec<String> = quest_ids.iter().map(i64::to_string).collect();
+                path.push(quest_ids[0].to_string());
+                write!(formatter, "Quests {} form a cycle.", path.join(" → "))
+            }
+            Self::UnknownStatus { quest_id, value } => {
+                write!(
+                    formatter,
+                    "Quest {} has an unknown status `{}`.",
+                    quest_id, value
+                )
+            }
+            Self::UnknownTier { quest_id, value } => {
+                write!(
+                    formatter,
+                    "Quest {} has an unknown tier `{}`.",
+                    quest_id, value
+                )
+            }
+            Self::Integrity(message) => write!(formatter, "Database integrity error: {}.", message),
+        }
+    }
+}
+
+/// How to fix quests whose parent quest does not exist.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OrphanFix {
+    /// Turn each orphan into a main quest
+    Reroot,
+    /// Delete each orphan and its secondary quests
+    Delete,
+}
+
+/// Finds and fixes problems in the quest log, such as orphans, parent cycles, and corrupt values.
+pub struct Doctor<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Doctor<'a> {
+    /// Constructs a new doctor.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Scans the quest log for problems.
+    pub fn diagnose(&self) -> Result<Vec<Problem>, QuestError> {
+        let mut problems = self.check_integrity()?;
+
+        // Read the raw rows, since corrupt values cannot be loaded as quests.
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, chain_id, status, tier FROM quest ORDER BY id")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, Option<i64>, i64, i64)>>>()?;
+
+        let parents: HashMap<i64, Option<i64>> = rows
+            .iter()
+            .map(|&(quest_id, chain_id, _, _)| (quest_id, chain_id))
+            .collect();
+
+        for &(quest_id, chain_id, status, tier) in &rows {
+            if let Some(chain_id) = chain_id {
+                if !parents.contains_key(&chain_id) {
+                    problems.push(Problem::Orphan { quest_id, chain_id });
+                }
+            }
+
+            if Status::try_from(status).is_err() {
+                problems.push(Problem::UnknownStatus {
+                    quest_id,
+                    value: status,
+                });
+            }
+
+            if Tier::try_from(tier).is_err() {
+                problems.push(Problem::UnknownTier {
+                    quest_id,
+                    value: tier,
+                });
+            }
+        }
+
+        problems.extend(Self::find_cycles(&parents));
+
+        Ok(problems)
+    }
+
+    /// Fixes the specified problems inside one transaction, and returns the number fixed. Orphans
+    /// are fixed with the specified strategy, cycles are broken by turning their oldest quest into
+    /// a main quest, and unknown values are reset to their defaults. Fixes cannot be undone, since
+    /// undoing them would bring back the invalid rows, so the undo journal is cleared, as its
+    /// older operations may refer to those rows too.
+    pub fn fix(&self, problems: &[Problem], orphan_fix: OrphanFix) -> Result<usize, QuestError> {
+        let tx = Transaction::new(self.conn)?;
+        let mut fixed = 0;
+
+        for problem in problems {
+            match problem {
+                Problem::Orphan { quest_id, .. } => match orphan_fix {
+                    OrphanFix::Reroot => {
+                        tx.execute("UPDATE quest SET chain_id = NULL WHERE id = ?1", [quest_id])?;
+                    }
+                    OrphanFix::Delete => {
+                        // Foreign keys cascade the deletion to the notes, tags, and history.
+                        tx.execute(
+                            "WITH RECURSIVE chain AS (
+                                SELECT id FROM quest WHERE id = ?1
+                                UNION
+                                SELECT quest.id FROM quest
+                                INNER JOIN chain ON quest.chain_id = chain.id
+                            ) DELETE FROM quest WHERE id IN chain",
+                            [quest_id],
+                        )?;
+                    }
+                },
+                Problem::Cycle { quest_ids } => {
+                    tx.execute(
+                        "UPDATE quest SET chain_id = NULL WHERE id = ?1",
+                        [quest_ids[0]],
+                    )?;
+                }
+                Problem::UnknownStatus { quest_id, .. } => {
+                    tx.execute(
+                        "UPDATE quest SET status = ?2 WHERE id = ?1",
+                        params![quest_id, Status::Pending as i64],
+                    )?;
+                }
+                Problem::UnknownTier { quest_id, .. } => {
+                    tx.execute(
+                        "UPDATE quest SET tier = ?2 WHERE id = ?1",
+                        params![quest_id, Tier::Common as i64],
+                    )?;
+                }
+                Problem::Integrity(_) => continue,
+            }
+
+            fixed += 1;
+        }
+
+        // Drop the tags of deleted orphans.
+        QuestDao::delete_unused_tags(&tx)?;
+        tx.execute("DELETE FROM undo_operation", [])?;
+        tx.commit()?;
+
+        Ok(fixed)
+    }
+
+    /// Runs the SQLite integrity check.
+    fn check_integrity(&self) -> Result<Vec<Problem>, QuestError> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let messages = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(messages
+            .into_iter()
+            .filter(|message| message != "ok")
+            .map(Problem::Integrity)
+            .collect())
+    }
+
+    /// Finds every parent cycle, given the parent of each quest. Each cycle is reported once,
+    /// starting with its oldest quest.
+    fn find_cycles(parents: &HashMap<i64, Option<i64>>) -> Vec<Problem> {
+        let mut quest_ids: Vec<i64> = parents.keys().copied().collect();
+        quest_ids.sort();
+
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+
+        for quest_id in quest_ids {
+            // Follow the parents until reaching a main quest, an orphan, or a visited quest.
+            let mut path = Vec::new();
+            let mut current = Some(quest_id);
+
+            while let Some(id) = current {
+                if !visited.insert(id) {
+                    // A cycle exists only if the visited quest is on the current path.
+                    if let Some(start) = path.iter().position(|&path_id| path_id == id) {
+                        let mut cycle: Vec<i64> = path[start..].to_vec();
+                        let oldest = cycle.iter().enumerate().min_by_key(|(_, &id)| id);
+                        let oldest = oldest.map(|(idx, _)| idx).unwrap_or(0);
+                        cycle.rotate_left(oldest);
+                        cycles.push(Problem::Cycle { quest_ids: cycle });
+                    }
+                    break;
+                }
+
+                path.push(id);
+                current = parents.get(&id).copied().flatten();
+            }
+        }
+
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quest::QuestDao;
+    use crate::testing::{add, open};
+    use crate::undo::UndoDao;
+
+    #[test]
+    fn undo_after_fix_leaves_the_journal_usable() {
+        let conn = open();
+        let main_id = add(&conn, "Main", None);
+        let orphan_id = add(&conn, "Orphan", Some(main_id));
+
+        // Detach the secondary quest from its parent behind the foreign keys.
+        conn.pragma_update(None, "foreign_keys", false).unwrap();
+        conn.execute("UPDATE quest SET chain_id = 99 WHERE id = ?1", [orphan_id])
+            .unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+
+        let doctor = Doctor::new(&conn);
+        let problems = doctor.diagnose().unwrap();
+        assert!(matches!(
+            problems.as_slice(),
+            [Problem::Orphan { quest_id, chain_id: 99 }] if *quest_id == orphan_id
+        ));
+        assert_eq!(doctor.fix(&problems, OrphanFix::Delete).unwrap(), 1);
+
+        let undo_dao = UndoDao::new(&conn);
+        assert!(undo_dao.undo().unwrap().is_none());
+        assert!(doctor.diagnose().unwrap().is_empty());
+
+        let quest_id = add(&conn, "Next", None);
+        assert!(undo_dao.undo().unwrap().is_some());
+        assert!(QuestDao::new(&conn).get_quest(quest_id).is_err());
+    }
+
+    #[test]
+    fn find_cycles_reports_each_cycle_once_from_its_oldest_quest() {
+        let parents = HashMap::from([
+            (1, None),
+            (2, Some(4)),
+            (3, Some(2)),
+            (4, Some(3)),
+            (5, Some(4)),
+        ]);
+
+        let cycles = Doctor::find_cycles(&parents);
+        assert!(matches!(
+            cycles.as_slice(),
+            [Problem::Cycle { quest_ids }] if quest_ids == &[2, 4, 3]
+        ));
+    }
+}
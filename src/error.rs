@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::process::ExitCode;
+
+/// An error raised while loading, storing, or validating quests.
+#[derive(Debug)]
+pub enum QuestError {
+    /// The quest does not exist.
+    NotFound(i64),
+    /// The parent quest does not exist or cannot hold the quest.
+    InvalidParent(i64),
+    /// The database could not be opened, read, or written.
+    StorageFailure(String),
+    /// A stored value could not be decoded.
+    CorruptValue { column: &'static str, value: i64 },
+    /// The text editor could not be run.
+    EditorFailure(String),
+    /// The named quest log does not exist.
+    UnknownLog(String),
+    /// The imported quests could not be read, or conflict with the quest log.
+    InvalidImport(String),
+    /// The quest log has no quests, but the command needs one.
+    EmptyLog,
+}
+
+impl QuestError {
+    /// The exit code reported when the quest does not exist.
+    pub const EXIT_NOT_FOUND: u8 = 3;
+    /// The exit code reported when the parent quest is invalid.
+    pub const EXIT_INVALID_PARENT: u8 = 4;
+    /// The exit code reported when the database cannot be accessed.
+    pub const EXIT_STORAGE_FAILURE: u8 = 5;
+    /// The exit code reported when the database holds a corrupt value.
+    pub const EXIT_CORRUPT_VALUE: u8 = 6;
+    /// The exit code reported when the text editor fails.
+    pub const EXIT_EDITOR_FAILURE: u8 = 7;
+    /// The exit code reported when the named quest log does not exist.
+    pub const EXIT_UNKNOWN_LOG: u8 = 8;
+    /// The exit code reported when the imported quests are invalid or conflict with the log.
+    pub const EXIT_INVALID_IMPORT: u8 = 9;
+    /// The exit code reported when the quest log has no quests.
+    pub const EXIT_EMPTY_LOG: u8 = 10;
+
+    /// Gets the process exit code for the error. Code 2 is reserved for usage errors reported by
+    /// the argument parser.
+    pub fn exit_code(&self) -> ExitCode {
+        let code = match self {
+            Self::NotFound(_) => Self::EXIT_NOT_FOUND,
+            Self::InvalidParent(_) => Self::EXIT_INVALID_PARENT,
+            Self::StorageFailure(_) => Self::EXIT_STORAGE_FAILURE,
+            Self::CorruptValue { .. } => Self::EXIT_CORRUPT_VALUE,
+            Self::EditorFailure(_) => Self::EXIT_EDITOR_FAILURE,
+            Self::UnknownLog(_) => Self::EXIT_UNKNOWN_LOG,
+            Self::InvalidImport(_) => Self::EXIT_INVALID_IMPORT,
+            Self::EmptyLog => Self::EXIT_EMPTY_LOG,
+        };
+        ExitCode::from(code)
+    }
+}
+
+impl Display for QuestError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound(quest_id) => write!(formatter, "Quest {} does not exist.", quest_id),
+            Self::InvalidParent(quest_id) => {
+                write!(formatter, "Quest {} cannot be used as a parent.", quest_id)
+            }
+            Self::StorageFailure(message) => {
+                write!(formatter, "Failed to access the quest log: {}.", message)
+            }
+            Self::CorruptValue { column, value } => write!(
+                formatter,
+                "The quest log contains an unknown {} `{}`. Run `quest doctor` to fix it.",
+                column, value
+            ),
+            Self::EditorFailure(message) => {
+                write!(formatter, "Failed to run the text editor: {}.", message)
+            }
+            Self::UnknownLog(name) => write!(
+                formatter,
+                "Quest log `{}` does not exist. Run `quest logs create {}` to create it.",
+                name, name
+            ),
+            Self::InvalidImport(message) => {
+                write!(formatter, "Failed to import the quests: {}.", message)
+            }
+            Self::EmptyLog => write!(formatter, "The quest log has no quests."),
+        }
+    }
+}
+
+impl Error for QuestError {}
+
+impl From<io::Error> for QuestError {
+    fn from(value: io::Error) -> Self {
+        Self::StorageFailure(value.to_string())
+    }
+}
+
+impl From<csv::Error> for QuestError {
+    fn from(value: csv::Error) -> Self {
+        Self::StorageFailure(value.to_string())
+    }
+}
+
+impl From<serde_json::Error> for QuestError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::StorageFailure(value.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for QuestError {
+    fn from(value: rusqlite::Error) -> Self {
+        match value {
+            // Decoding errors raised by `FromSql` implementations carry the original error.
+            rusqlite::Error::FromSqlConversionFailure(_, _, err) => match err.downcast() {
+                Ok(err) => *err,
+                Err(err) => Self::StorageFailure(err.to_string()),
+            },
+            _ => Self::StorageFailure(value.to_string()),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::ValueEnum;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::date::{self, DATE_FORMAT};
+use crate::error::QuestError;
+use crate::ical;
+use crate::log::NoteDao;
+use crate::quest::{Chain, QuestDao, Status, Tier};
+use crate::todotxt;
+
+/// The version of the export schema. It only changes when a field is removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The export schema, documented in the long help of `quest export`.
+pub const SCHEMA: &str = "\
+Schema (version 1):
+  json    An object with `schema` (the schema version), `exported_at`, and `quests`, the list
+          of main quests. Each quest holds its secondary quests in `quests`.
+  ndjson  One quest per line, without `quests`. Parents come before their secondary quests.
+  csv     One quest per row, after a header row, without `notes` or `history`. Parents come
+          before their secondary quests.
+
+todo.txt writes one task per line. Tiers become the priorities (A) to (D), finished quests are
+marked done with their completion date, tags become +projects, or @contexts if they start with @,
+and the `id:`, `parent:`, `due:`, `t:` (start), `status:`, and `pri:` keys hold the rest.
+
+ical writes one VTODO per quest. Statuses become STATUS (NEEDS-ACTION, IN-PROCESS, COMPLETED,
+or CANCELLED), tiers become PRIORITY (1, 3, 5, or 9), tags become CATEGORIES, notes become the
+DESCRIPTION, and secondary quests refer to their parent with RELATED-TO. Imported descriptions
+become one note, and tasks whose parent is not in the file become main quests and are reported.
+
+taskwarrior reads the JSON written by `task export`. A task becomes the parent quest of the tasks
+it depends on, and a recurring task the parent of its instances. Priorities H, M, and L become
+legendary, epic, and rare, started tasks become ongoing, deleted tasks become abandoned, projects
+become tags, annotations become notes, and the wait or scheduled date becomes the start date.
+Anything else is reported. Taskwarrior files are never guessed from the file extension.
+
+Markdown and Org export each quest chain as a nested checklist for reading, with notes below
+each quest. They cannot be imported.
+
+Quest fields:
+  id          Identifier
+  parent_id   Identifier of the parent quest, or null for a main quest
+  objective   Objective
+  status      pending, ongoing, completed, waiting, or abandoned
+  tier        common, rare, epic, or legendary
+  due         Due date as YYYY-MM-DD, or null
+  start       Start date as YYYY-MM-DD, or null
+  created_at  RFC 3339 creation time, or null if unknown
+  updated_at  RFC 3339 modification time, or null if unknown
+  tags        Tag names, separated by spaces in CSV
+  notes       Journal notes, each with `created_at` and `text`
+  history     Status changes, each with `status` and `changed_at`
+
+Null values are empty in CSV. New fields may be added without changing the version.";
+
+/// A format for exporting and importing quests.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One JSON document, with secondary quests nested in their parents
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values, with a parent_id column
+    Csv,
+    /// A Markdown checklist, with secondary quests nested in their parents
+    Markdown,
+    /// An Org-mode checklist, with secondary quests nested in their parents
+    Org,
+    /// One todo.txt task per line, linked to its parent with a parent:<id> key
+    Todotxt,
+    /// An iCalendar object with one VTODO per quest, linked to its parent with RELATED-TO
+    Ical,
+    /// The JSON written by `task export`, which can only be imported
+    Taskwarrior,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "csv" => Some(Self::Csv),
+            "md" | "markdown" => Some(Self::Markdown),
+            "org" => Some(Self::Org),
+            "txt" => Some(Self::Todotxt),
+            "ics" => Some(Self::Ical),
+            _ => None,
+        }
+    }
+
+    /// Checks if quests can be exported in the format. Taskwarrior files can only be imported.
+    pub fn is_exportable(self) -> bool {
+        self != Self::Taskwarrior
+    }
+
+    /// Builds the parser of the `--format` of `quest export`, which only accepts the formats that
+    /// can be exported.
+    pub fn export_parser() -> impl TypedValueParser<Value = Self> {
+        let formats = Self::value_variants()
+            .iter()
+            .filter(|format| format.is_exportable())
+            .filter_map(ValueEnum::to_possible_value);
+
+        PossibleValuesParser::new(formats).try_map(|name| Self::from_str(&name, true))
+    }
+}
+
+/// An exported quest log.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogRecord {
+    pub schema: u32,
+    pub exported_at: DateTime<Utc>,
+    pub quests: Vec<QuestRecord>,
+}
+
+/// An exported quest. Secondary quests are only nested in the JSON format.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct QuestRecord {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub objective: String,
+    pub status: Status,
+    pub tier: Tier,
+    pub due: Option<NaiveDate>,
+    pub start: Option<NaiveDate>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Vec<NoteRecord>,
+    #[serde(default)]
+    pub history: Vec<TransitionRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quests: Option<Vec<QuestRecord>>,
+}
+
+/// An exported journal note.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NoteRecord {
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+}
+
+/// An exported status change.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TransitionRecord {
+    pub status: Status,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// An exported quest in the CSV format, which cannot hold lists.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CsvRecord {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub objective: String,
+    pub status: Status,
+    pub tier: Tier,
+    pub due: Option<NaiveDate>,
+    pub start: Option<NaiveDate>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub tags: String,
+}
+
+impl QuestRecord {
+    /// Gets when the quest was last completed or abandoned, falling back to when it was last
+    /// updated if its history does not say.
+    pub fn closed_at(&self) -> Option<DateTime<Utc>> {
+        self.history
+            .iter()
+            .rev()
+            .find(|transition| matches!(transition.status, Status::Completed | Status::Abandoned))
+            .map(|transition| transition.changed_at)
+            .or(self.updated_at)
+    }
+}
+
+impl From<QuestRecord> for CsvRecord {
+    fn from(quest: QuestRecord) -> Self {
+        Self {
+            id: quest.id,
+            parent_id: quest.parent_id,
+            objective: quest.objective,
+            status: quest.status,
+            tier: quest.tier,
+            due: quest.due,
+            start: quest.start,
+            created_at: quest.created_at,
+            updated_at: quest.updated_at,
+            tags: quest.tags.join(" "),
+        }
+    }
+}
+
+impl From<CsvRecord> for QuestRecord {
+    fn from(quest: CsvRecord) -> Self {
+        Self {
+            id: quest.id,
+            parent_id: quest.parent_id,
+            objective: quest.objective,
+            status: quest.status,
+            tier: quest.tier,
+            due: quest.due,
+            start: quest.start,
+            created_at: quest.created_at,
+            updated_at: quest.updated_at,
+            tags: quest.tags.split_whitespace().map(str::to_owned).collect(),
+            notes: Vec::new(),
+            history: Vec::new(),
+            quests: None,
+        }
+    }
+}
+
+/// Writes the quest log in an interchange format.
+pub struct Exporter<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Exporter<'a> {
+    /// Constructs a new exporter.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Writes every quest chain, including finished and deferred quests, in the specified format.
+    /// Quests in the trash are left out.
+    pub fn export(&self, format: Format, mut writer: impl Write) -> Result<(), QuestError> {
+        let quests = self.get_records()?;
+
+        match format {
+            Format::Json => {
+                let log = LogRecord {
+                    schema: SCHEMA_VERSION,
+                    exported_at: Utc::now(),
+                    quests,
+                };
+                serde_json::to_writer_pretty(&mut writer, &log)?;
+                writeln!(writer)?;
+            }
+            Format::Ndjson => {
+                for quest in flatten(quests) {
+                    serde_json::to_writer(&mut writer, &quest)?;
+                    writeln!(writer)?;
+                }
+            }
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                for quest in flatten(quests) {
+                    writer.serialize(CsvRecord::from(quest))?;
+                }
+                writer.flush()?;
+            }
+            Format::Todotxt => todotxt::write(&mut writer, &flatten(quests))?,
+            Format::Ical => ical::write(&mut writer, &flatten(quests))?,
+            Format::Taskwarrior => unreachable!("Taskwarrior files can only be imported"),
+            Format::Markdown | Format::Org => {
+                for quest in &quests {
+                    Self::write_checklist(&mut writer, format, quest, 0)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a quest as a checklist item, followed by its notes and, nested one level deeper, its
+    /// secondary quests. Finished quests are checked, and abandoned quests are struck through.
+    fn write_checklist(
+        writer: &mut impl Write,
+        format: Format,
+        quest: &QuestRecord,
+        depth: usize,
+    ) -> Result<(), QuestError> {
+        let indent = "  ".repeat(depth);
+        let is_org = format == Format::Org;
+
+        let checkbox = match quest.status {
+            Status::Completed | Status::Abandoned if is_org => "[X]",
+            Status::Completed | Status::Abandoned => "[x]",
+            Status::Ongoing if is_org => "[-]",
+            _ => "[ ]",
+        };
+        let objective = match quest.status {
+            Status::Abandoned if is_org => format!("+{}+", quest.objective),
+            Status::Abandoned => format!("~~{}~~", quest.objective),
+            _ => quest.objective.clone(),
+        };
+        let badge = if is_org {
+            format!("~{}~", quest.tier)
+        } else {
+            format!("`{}`", quest.tier)
+        };
+
+        // Show the identifier, and whatever the checkbox cannot.
+        let mut details = vec![format!("quest {}", quest.id)];
+        if matches!(
+            quest.status,
+            Status::Ongoing | Status::Waiting | Status::Abandoned
+        ) {
+            details.push(quest.status.to_string().to_lowercase());
+        }
+        if let Some(due) = quest.due {
+            if is_org {
+                details.push(format!("due <{}>", due.format("%Y-%m-%d %a")));
+            } else {
+                details.push(format!("due {}", due.format(DATE_FORMAT)));
+            }
+        }
+        details.extend(quest.tags.iter().map(|tag| format!("+{}", tag)));
+
+        writeln!(
+            writer,
+            "{}- {} {} {} ({})",
+            indent,
+            checkbox,
+            objective,
+            badge,
+            details.join(", ")
+        )?;
+
+        for (idx, note) in quest.notes.iter().enumerate() {
+            if is_org {
+                let created_at = note.created_at.with_timezone(&Local);
+                let mut prefix = format!("[{}] ", created_at.format("%Y-%m-%d %a %H:%M"));
+                for line in note.text.lines() {
+                    writeln!(writer, "{}  {}{}", indent, prefix, line)?;
+                    prefix.clear();
+                }
+            } else {
+                if idx > 0 {
+                    writeln!(writer, "{}  >", indent)?;
+                }
+                let mut prefix = format!("**{}** ", date::format_datetime(note.created_at));
+                for line in note.text.lines() {
+                    writeln!(writer, "{}  > {}{}", indent, prefix, line)?;
+                    prefix.clear();
+                }
+            }
+        }
+
+        for secondary_quest in quest.quests.iter().flatten() {
+            Self::write_checklist(writer, format, secondary_quest, depth + 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets every quest chain as nested records.
+    fn get_records(&self) -> Result<Vec<QuestRecord>, QuestError> {
+        let quest_dao = QuestDao::new(self.conn);
+        let chains = quest_dao.get_all_chains()?;
+
+        chains.iter().map(|chain| self.to_record(chain)).collect()
+    }
+
+    /// Converts a quest chain, with its notes and status history, into a nested record.
+    fn to_record(&self, chain: &Chain) -> Result<QuestRecord, QuestError> {
+        let quest = chain.main();
+        let quest_dao = QuestDao::new(self.conn);
+        let note_dao = NoteDao::new(self.conn);
+
+        let notes = note_dao
+            .get_notes(quest.id())?
+            .into_iter()
+            .map(|note| NoteRecord {
+                created_at: note.created_at(),
+                text: note.text().clone(),
+            })
+            .collect();
+        let history = quest_dao
+            .get_history(quest.id())?
+            .into_iter()
+            .map(|transition| TransitionRecord {
+                status: transition.status(),
+                changed_at: transition.changed_at(),
+            })
+            .collect();
+        let quests = chain
+            .chains()
+            .iter()
+            .map(|chain| self.to_record(chain))
+            .collect::<Result<Vec<QuestRecord>, QuestError>>()?;
+
+        Ok(QuestRecord {
+            id: quest.id(),
+            parent_id: quest.chain_id(),
+            objective: quest.objective().clone(),
+            status: quest.status(),
+            tier: quest.tier(),
+            due: quest.due(),
+            start: quest.start(),
+            created_at: quest.created_at(),
+            updated_at: quest.updated_at(),
+            tags: quest.tags().clone(),
+            notes,
+            history,
+            quests: Some(quests),
+        })
+    }
+}
+
+/// Flattens nested records into a list, with parents before their secondary quests. Each secondary
+/// quest refers to the quest it was nested in.
+pub fn flatten(quests: Vec<QuestRecord>) -> Vec<QuestRecord> {
+    let mut flattened = Vec::new();
+
+    for mut quest in quests {
+        let mut secondary_quests = quest.quests.take().unwrap_or_default();
+        for secondary_quest in &mut secondary_quests {
+            secondary_quest.parent_id = Some(quest.id);
+        }
+
+        flattened.push(quest);
+        flattened.extend(flatten(secondary_quests));
+    }
+
+    flattened
+}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+
+use crate::error::QuestError;
+use crate::export::{NoteRecord, QuestRecord};
+use crate::quest::{Status, Tier};
+
+/// The longest content line, in bytes, before it is folded.
+const LINE_LENGTH: usize = 75;
+
+/// The format of UTC timestamps.
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The format of dates.
+const DATE_FORMAT: &str = "%Y%m%d";
+
+/// Writes quests as an iCalendar object with one VTODO per quest. Statuses become STATUS, tiers
+/// become PRIORITY, tags become CATEGORIES, notes become the DESCRIPTION, and secondary quests refer
+/// to their parent with RELATED-TO.
+pub fn write(writer: &mut impl Write, quests: &[QuestRecord]) -> Result<(), QuestError> {
+    let now = Utc::now();
+
+    write_line(writer, "BEGIN:VCALENDAR")?;
+    write_line(writer, "VERSION:2.0")?;
+    write_line(
+        writer,
+        &format!("PRODID:-//quest//quest {}//EN", env!("CARGO_PKG_VERSION")),
+    )?;
+
+    for quest in quests {
+        write_line(writer, "BEGIN:VTODO")?;
+        write_line(writer, &format!("UID:{}", uid(quest.id)))?;
+        write_line(writer, &format!("DTSTAMP:{}", now.format(DATETIME_FORMAT)))?;
+        write_line(writer, &format!("SUMMARY:{}", escape(&quest.objective)))?;
+        write_line(writer, &format!("STATUS:{}", status(quest.status)))?;
+        write_line(writer, &format!("PRIORITY:{}", priority(quest.tier)))?;
+
+        // Waiting quests need action as far as calendars know, so keep the real status aside.
+        if quest.status == Status::Waiting {
+            write_line(writer, "X-QUEST-STATUS:WAITING")?;
+        }
+        if let Some(created_at) = quest.created_at {
+            write_line(
+                writer,
+                &format!("CREATED:{}", created_at.format(DATETIME_FORMAT)),
+            )?;
+        }
+        if let Some(updated_at) = quest.updated_at {
+            write_line(
+                writer,
+                &format!("LAST-MODIFIED:{}", updated_at.format(DATETIME_FORMAT)),
+            )?;
+        }
+        if let (Status::Completed, Some(closed_at)) = (quest.status, quest.closed_at()) {
+            write_line(
+                writer,
+                &format!("COMPLETED:{}", closed_at.format(DATETIME_FORMAT)),
+            )?;
+        }
+        if let Some(start) = quest.start {
+            write_line(
+                writer,
+                &format!("DTSTART;VALUE=DATE:{}", start.format(DATE_FORMAT)),
+            )?;
+        }
+        if let Some(due) = quest.due {
+            write_line(
+                writer,
+                &format!("DUE;VALUE=DATE:{}", due.format(DATE_FORMAT)),
+            )?;
+        }
+        if !quest.tags.is_empty() {
+            let tags: Vec<String> = quest.tags.iter().map(|tag| escape(tag)).collect();
+            write_line(writer, &format!("CATEGORIES:{}", tags.join(",")))?;
+        }
+        if !quest.notes.is_empty() {
+            let notes: Vec<&str> = quest.notes.iter().map(|note| note.text.as_str()).collect();
+            write_line(
+                writer,
+                &format!("DESCRIPTION:{}", escape(&notes.join("\n\n"))),
+            )?;
+        }
+        if let Some(parent_id) = quest.parent_id {
+            write_line(
+                writer,
+                &format!("RELATED-TO;RELTYPE=PARENT:{}", uid(parent_id)),
+            )?;
+        }
+        write_line(writer, "END:VTODO")?;
+    }
+
+    write_line(writer, "END:VCALENDAR")?;
+
+    Ok(())
+}
+
+/// Reads the VTODOs of an iCalendar object as quests, reversing `write`, along with a description
+/// of everything that could not be mapped. Other components, such as events and alarms, are
+/// skipped. Tasks are identified by their position, and secondary tasks are linked to the task
+/// whose UID they refer to with a parent RELATED-TO. Tasks whose parent is not in the file become
+/// main quests.
+pub fn read(reader: impl Read) -> Result<(Vec<QuestRecord>, Vec<String>), QuestError> {
+    let mut quests = Vec::new();
+    let mut unmapped = Vec::new();
+
+    // Maps the UID of each task to its identifier, and each task to its UID and its parent's.
+    let mut quest_ids: HashMap<String, i64> = HashMap::new();
+    let mut uids: Vec<(Option<String>, Option<String>)> = Vec::new();
+
+    // The properties of the task being read, and the components open inside it.
+    let mut todo: Option<Vec<Property>> = None;
+    let mut depth = 0;
+
+    for line in unfold(reader)? {
+        let property = Property::parse(&line)?;
+
+        match (property.name.as_str(), todo.as_mut()) {
+            ("BEGIN", None) if property.value.eq_ignore_ascii_case("VTODO") => {
+                todo = Some(Vec::new());
+            }
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(_)) => {
+                let id = quests.len() as i64 + 1;
+                let properties = todo.take().unwrap_or_default();
+                let (quest, uid, parent_uid) = to_record(id, properties)?;
+
+                if let Some(uid) = &uid {
+                    quest_ids.insert(uid.clone(), id);
+                }
+                uids.push((uid, parent_uid));
+                quests.push(quest);
+            }
+            (_, Some(properties)) if depth == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+
+    for (quest, (uid, parent_uid)) in quests.iter_mut().zip(uids) {
+        let Some(parent_uid) = parent_uid else {
+            continue;
+        };
+
+        match quest_ids.get(&parent_uid) {
+            Some(&parent_id) => quest.parent_id = Some(parent_id),
+            None => unmapped.push(format!(
+                "task {} `{}`: parent {}, which is not imported",
+                uid.as_deref().unwrap_or("?"),
+                quest.objective,
+                parent_uid
+            )),
+        }
+    }
+
+    Ok((quests, unmapped))
+}
+
+/// A content line, split into its name, parameters, and value.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    /// Parses an unfolded content line. Parameter values may be quoted to hold colons.
+    fn parse(line: &str) -> Result<Self, QuestError> {
+        let mut in_quotes = false;
+        let colon = line
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                c == ':' && !in_quotes
+            })
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| QuestError::InvalidImport(format!("invalid content line `{}`", line)))?;
+
+        let mut parts = line[..colon].split(';');
+        let name = parts.next().unwrap_or_default().to_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_owned()))
+            .collect();
+
+        Ok(Self {
+            name,
+            params,
+            value: line[colon + 1..].to_owned(),
+        })
+    }
+
+    /// Gets the value of a parameter, if set.
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Converts the properties of a task into a quest, along with its UID and the UID of its parent.
+fn to_record(
+    id: i64,
+    properties: Vec<Property>,
+) -> Result<(QuestRecord, Option<String>, Option<String>), QuestError> {
+    let mut quest = QuestRecord {
+        id,
+        parent_id: None,
+        objective: String::new(),
+        status: Status::Pending,
+        tier: Tier::Common,
+        due: None,
+        start: None,
+        created_at: None,
+        updated_at: None,
+        tags: Vec::new(),
+        notes: Vec::new(),
+        history: Vec::new(),
+        quests: None,
+    };
+    let mut uid = None;
+    let mut parent_uid = None;
+    let mut description = None;
+    let mut is_waiting = false;
+
+    for property in properties {
+        let value = property.value.as_str();
+
+        match property.name.as_str() {
+            "UID" => uid = Some(value.to_owned()),
+            "SUMMARY" => quest.objective = unescape(value),
+            "STATUS" => quest.status = parse_status(value)?,
+            "X-QUEST-STATUS" => is_waiting = value.eq_ignore_ascii_case("WAITING"),
+            "PRIORITY" => quest.tier = parse_priority(value)?,
+            "CREATED" => quest.created_at = Some(parse_datetime(value)?),
+            "LAST-MODIFIED" => quest.updated_at = Some(parse_datetime(value)?),
+            "DTSTART" => quest.start = Some(parse_date(value)?),
+            "DUE" => quest.due = Some(parse_date(value)?),
+            "DESCRIPTION" => description = Some(unescape(value)),
+            "CATEGORIES" => {
+                // Tag names may not contain whitespace, which categories often do.
+                let tags = split_list(value)
+                    .into_iter()
+                    .map(|tag| tag.split_whitespace().collect::<Vec<&str>>().join("-"))
+                    .filter(|tag| !tag.is_empty());
+                quest.tags.extend(tags);
+            }
+            "RELATED-TO" => {
+                let reltype = property.param("RELTYPE").unwrap_or("PARENT");
+                if reltype.eq_ignore_ascii_case("PARENT") {
+                    parent_uid = Some(value.to_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if is_waiting && quest.status == Status::Pending {
+        quest.status = Status::Waiting;
+    }
+    if quest.objective.trim().is_empty() {
+        return Err(QuestError::InvalidImport(format!(
+            "task {} has no summary",
+            uid.as_deref().unwrap_or("without a UID")
+        )));
+    }
+
+    // The description becomes one note, written when the task was last changed.
+    if let Some(text) = description.filter(|text| !text.trim().is_empty()) {
+        let created_at = quest.updated_at.or(quest.created_at).unwrap_or(Utc::now());
+        quest.notes.push(NoteRecord { created_at, text });
+    }
+
+    Ok((quest, uid, parent_uid))
+}
+
+/// Gets the UID of a quest.
+fn uid(quest_id: i64) -> String {
+    format!("quest-{}", quest_id)
+}
+
+/// Gets the iCalendar status of a quest status.
+fn status(status: Status) -> &'static str {
+    match status {
+        Status::Pending | Status::Waiting => "NEEDS-ACTION",
+        Status::Ongoing => "IN-PROCESS",
+        Status::Completed => "COMPLETED",
+        Status::Abandoned => "CANCELLED",
+    }
+}
+
+/// Parses an iCalendar status into a quest status.
+fn parse_status(value: &str) -> Result<Status, QuestError> {
+    match value.to_uppercase().as_str() {
+        "NEEDS-ACTION" => Ok(Status::Pending),
+        "IN-PROCESS" => Ok(Status::Ongoing),
+        "COMPLETED" => Ok(Status::Completed),
+        "CANCELLED" => Ok(Status::Abandoned),
+        _ => Err(QuestError::InvalidImport(format!(
+            "unknown task status `{}`",
+            value
+        ))),
+    }
+}
+
+/// Gets the iCalendar priority of a tier, where 1 is the highest.
+fn priority(tier: Tier) -> u8 {
+    match tier {
+        Tier::Legendary => 1,
+        Tier::Epic => 3,
+        Tier::Rare => 5,
+        Tier::Common => 9,
+    }
+}
+
+/// Parses an iCalendar priority into a tier. Undefined and low priorities are common.
+fn parse_priority(value: &str) -> Result<Tier, QuestError> {
+    let priority: u8 = value
+        .trim()
+        .parse()
+        .map_err(|_| QuestError::InvalidImport(format!("invalid priority `{}`", value)))?;
+
+    Ok(match priority {
+        1 => Tier::Legendary,
+        2..=4 => Tier::Epic,
+        5..=6 => Tier::Rare,
+        _ => Tier::Common,
+    })
+}
+
+/// Parses a date, or the date of a timestamp, such as `20250314` or `20250314T090000Z`.
+fn parse_date(value: &str) -> Result<NaiveDate, QuestError> {
+    value
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, DATE_FORMAT).ok())
+        .ok_or_else(|| QuestError::InvalidImport(format!("invalid date `{}`", value)))
+}
+
+/// Parses a timestamp in UTC, or in local time if it has no `Z` suffix.
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>, QuestError> {
+    let invalid = || QuestError::InvalidImport(format!("invalid timestamp `{}`", value));
+    let datetime = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .map_err(|_| invalid())?;
+
+    if value.ends_with('Z') {
+        Ok(datetime.and_utc())
+    } else {
+        datetime
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|datetime| datetime.to_utc())
+            .ok_or_else(invalid)
+    }
+}
+
+/// Escapes text for a property value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Unescapes a property value.
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(c) => text.push(c),
+            None => {}
+        }
+    }
+
+    text
+}
+
+/// Splits a list value at its unescaped commas, and unescapes each item.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut is_escaped = false;
+
+    for (idx, c) in value.char_indices() {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            ',' => {
+                items.push(unescape(&value[start..idx]));
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(unescape(&value[start..]));
+
+    items
+}
+
+/// Writes a content line, folding it into lines of at most 75 bytes.
+fn write_line(writer: &mut impl Write, line: &str) -> Result<(), QuestError> {
+    let mut rest = line;
+    let mut limit = LINE_LENGTH;
+
+    while rest.len() > limit {
+        // Never split a character.
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        write!(writer, "{}\r\n ", &rest[..end])?;
+        rest = &rest[end..];
+
+        // Continuation lines start with a space.
+        limit = LINE_LENGTH - 1;
+    }
+
+    write!(writer, "{}\r\n", rest)?;
+
+    Ok(())
+}
+
+/// Reads the content lines, joining folded lines back together.
+fn unfold(reader: impl Read) -> Result<Vec<String>, QuestError> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_owned()),
+        }
+    }
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn fold(line: &str) -> String {
+        let mut output = Vec::new();
+        write_line(&mut output, line).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_bytes() {
+        let line = "x".repeat(LINE_LENGTH);
+        assert_eq!(fold(&line), format!("{}\r\n", line));
+
+        let line = "x".repeat(200);
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= LINE_LENGTH));
+        assert_eq!(unfold(folded.as_bytes()).unwrap(), [line]);
+    }
+
+    #[test]
+    fn never_folds_inside_a_character() {
+        for line in [
+            "é".repeat(100),
+            format!("x{}", "€".repeat(60)),
+            "🐉".repeat(40),
+        ] {
+            let folded = fold(&line);
+
+            // A split character would leave invalid UTF-8, which `fold` fails on.
+            assert!(folded.split("\r\n").all(|part| part.len() <= LINE_LENGTH));
+            assert_eq!(unfold(folded.as_bytes()).unwrap(), [line]);
+        }
+    }
+
+    #[test]
+    fn unfolds_spaces_tabs_and_bare_newlines() {
+        let input = "SUMMARY:Slay\r\n  the\n\tdragon\r\n\r\nSTATUS:COMPLETED\n";
+
+        assert_eq!(
+            unfold(input.as_bytes()).unwrap(),
+            ["SUMMARY:Slay thedragon", "STATUS:COMPLETED"]
+        );
+    }
+
+    #[test]
+    fn escapes_and_splits_lists() {
+        let text = "a,b;c\\d\ne";
+        assert_eq!(unescape(&escape(text)), text);
+
+        assert_eq!(split_list("a\\,b,c"), ["a,b", "c"]);
+        assert_eq!(split_list("a\\\\,b"), ["a\\", "b"]);
+        assert_eq!(split_list("one"), ["one"]);
+        assert_eq!(split_list(",trailing\\"), ["", "trailing"]);
+    }
+
+    #[test]
+    fn parses_quoted_parameters() {
+        let property = Property::parse("related-to;RELTYPE=PARENT;X-NOTE=\"a:b\":quest-1").unwrap();
+
+        assert_eq!(property.name, "RELATED-TO");
+        assert_eq!(property.param("RELTYPE"), Some("PARENT"));
+        assert_eq!(property.param("X-NOTE"), Some("a:b"));
+        assert_eq!(property.value, "quest-1");
+        assert!(Property::parse("no colon").is_err());
+    }
+
+    #[test]
+    fn maps_priorities_to_tiers() {
+        let tiers: Vec<Tier> = ["0", "1", "2", "4", "5", "6", "7", "9"]
+            .into_iter()
+            .map(|value| parse_priority(value).unwrap())
+            .collect();
+
+        assert_eq!(
+            tiers,
+            [
+                Tier::Common,
+                Tier::Legendary,
+                Tier::Epic,
+                Tier::Epic,
+                Tier::Rare,
+                Tier::Rare,
+                Tier::Common,
+                Tier::Common
+            ]
+        );
+        assert!(parse_priority("high").is_err());
+    }
+
+    #[test]
+    fn reads_only_the_properties_of_todos() {
+        let input = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Feast\r\nEND:VEVENT\r\n\
+            BEGIN:VTODO\r\nUID:a\r\nSUMMARY:Slay the dragon\r\n\
+            BEGIN:VALARM\r\nDESCRIPTION:Wake up\r\nEND:VALARM\r\n\
+            CATEGORIES:Boss Fight,cave\r\nDUE:20250314T090000Z\r\nEND:VTODO\r\n\
+            BEGIN:VTODO\r\nSUMMARY:Forge\r\nRELATED-TO:a\r\nEND:VTODO\r\n\
+            END:VCALENDAR\r\n";
+        let (quests, unmapped) = read(input.as_bytes()).unwrap();
+        assert!(unmapped.is_empty());
+
+        assert_eq!(quests.len(), 2);
+        assert_eq!(quests[0].objective, "Slay the dragon");
+        assert!(quests[0].notes.is_empty());
+        assert_eq!(quests[0].tags, ["Boss-Fight", "cave"]);
+        assert_eq!(quests[0].due, NaiveDate::from_ymd_opt(2025, 3, 14));
+        assert_eq!(quests[1].parent_id, Some(quests[0].id));
+    }
+
+    #[test]
+    fn reports_parents_that_are_not_imported() {
+        let input = "BEGIN:VTODO\r\nUID:b\r\nSUMMARY:Forge\r\nRELATED-TO:missing\r\nEND:VTODO\r\n";
+        let (quests, unmapped) = read(input.as_bytes()).unwrap();
+
+        assert_eq!(quests.len(), 1);
+        assert_eq!(quests[0].parent_id, None);
+        assert_eq!(
+            unmapped,
+            ["task b `Forge`: parent missing, which is not imported"]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_write_and_read() {
+        let mut quests = testing::chain();
+        quests[0].objective = format!("Slay the {} dragon, twice", "very ".repeat(20));
+
+        let read = testing::round_trip(write, |input| Ok(read(input)?.0), &quests);
+        testing::assert_same_quests(&read, &quests);
+
+        // Timestamps and notes are kept too, unlike in todo.txt.
+        for (read, quest) in read.iter().zip(&quests) {
+            assert_eq!(read.created_at, quest.created_at);
+            assert_eq!(read.updated_at, quest.updated_at);
+
+            let texts: Vec<&str> = read.notes.iter().map(|note| note.text.as_str()).collect();
+            let expected: Vec<&str> = quest.notes.iter().map(|note| note.text.as_str()).collect();
+            assert_eq!(texts, expected);
+        }
+    }
+
+    #[test]
+    fn takes_the_completion_time_from_the_history() {
+        let mut output = Vec::new();
+        write(&mut output, &[testing::edited_after_completion()]).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\r\nCOMPLETED:20250202T120000Z\r\n"));
+        assert!(output.contains("\r\nLAST-MODIFIED:20250505T120000Z\r\n"));
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::database::Transaction;
+use crate::error::QuestError;
+use crate::export::{self, CsvRecord, Format, LogRecord, QuestRecord, SCHEMA_VERSION};
+use crate::ical;
+use crate::tag;
+use crate::taskwarrior;
+use crate::todotxt;
+
+/// The outcome of an import.
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    imported: usize,
+    merged: usize,
+}
+
+impl ImportReport {
+    /// Copies the number of quests added to the log.
+    pub fn imported(&self) -> usize {
+        self.imported
+    }
+
+    /// Copies the number of quests that were already in the log.
+    pub fn merged(&self) -> usize {
+        self.merged
+    }
+}
+
+/// Recreates quests, read from an interchange format, in the quest log.
+pub struct Importer<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Importer<'a> {
+    /// Constructs a new importer.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Reads quest records in the specified format, along with a description of everything in the
+    /// file that could not be mapped onto quests.
+    pub fn read(
+        format: Format,
+        reader: impl Read,
+    ) -> Result<(Vec<QuestRecord>, Vec<String>), QuestError> {
+        let quests = match format {
+            Format::Json => {
+                let log: LogRecord = serde_json::from_reader(reader)
+                    .map_err(|err| QuestError::InvalidImport(err.to_string()))?;
+                if log.schema > SCHEMA_VERSION {
+                    return Err(QuestError::InvalidImport(format!(
+                        "schema version {} is newer than the supported version {}",
+                        log.schema, SCHEMA_VERSION
+                    )));
+                }
+                export::flatten(log.quests)
+            }
+            Format::Ndjson => {
+                let mut quests = Vec::new();
+                for (idx, line) in BufReader::new(reader).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let quest = serde_json::from_str(&line).map_err(|err| {
+                        QuestError::InvalidImport(format!("line {}: {}", idx + 1, err))
+                    })?;
+                    quests.push(quest);
+                }
+                quests
+            }
+            Format::Csv => csv::Reader::from_reader(reader)
+                .deserialize::<CsvRecord>()
+                .map(|quest| quest.map(QuestRecord::from))
+                .collect::<Result<Vec<QuestRecord>, csv::Error>>()
+                .map_err(|err| QuestError::InvalidImport(err.to_string()))?,
+            Format::Todotxt => todotxt::read(reader)?,
+            Format::Ical => return ical::read(reader),
+            Format::Taskwarrior => return taskwarrior::read(reader),
+            Format::Markdown | Format::Org => {
+                return Err(QuestError::InvalidImport(
+                    "Markdown and Org checklists cannot be imported".to_owned(),
+                ))
+            }
+        };
+
+        Ok((quests, Vec::new()))
+    }
+
+    /// Adds the quests to the log inside one transaction, with their notes, history, and tags, and
+    /// returns what was imported. Quests get new identifiers, and secondary quests follow their
+    /// parents. A quest with the same objective and creation time as a quest in the log is a
+    /// conflict: it is merged into the existing quest if specified, and fails the import
+    /// otherwise. A dry run rolls the transaction back.
+    pub fn import(
+        &self,
+        quests: &[QuestRecord],
+        merge: bool,
+        dry_run: bool,
+    ) -> Result<ImportReport, QuestError> {
+        let quests = Self::sort(quests)?;
+        let tx = Transaction::new(self.conn)?;
+
+        // Maps the identifier of each quest in the file to its identifier in the log.
+        let mut quest_ids: HashMap<i64, i64> = HashMap::new();
+        let mut report = ImportReport::default();
+
+        for quest in quests {
+            let chain_id = quest.parent_id.map(|parent_id| quest_ids[&parent_id]);
+
+            if let Some(quest_id) = Self::find_existing(&tx, quest)? {
+                if !merge {
+                    return Err(QuestError::InvalidImport(format!(
+                        "quest {} already exists as quest {}; use --merge to skip such quests",
+                        quest.id, quest_id
+                    )));
+                }
+
+                quest_ids.insert(quest.id, quest_id);
+                report.merged += 1;
+                continue;
+            }
+
+            let quest_id = Self::insert(&tx, quest, chain_id)?;
+            quest_ids.insert(quest.id, quest_id);
+            report.imported += 1;
+        }
+
+        if !dry_run {
+            tx.commit()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Orders the quests so that parents come before their secondary quests, and checks that
+    /// every parent is imported too.
+    fn sort(quests: &[QuestRecord]) -> Result<Vec<&QuestRecord>, QuestError> {
+        let mut ids = HashSet::new();
+        for quest in quests {
+            if !ids.insert(quest.id) {
+                return Err(QuestError::InvalidImport(format!(
+                    "quest {} appears more than once",
+                    quest.id
+                )));
+            }
+        }
+
+        if let Some(quest) = quests.iter().find(|quest| {
+            quest
+                .parent_id
+                .is_some_and(|parent_id| !ids.contains(&parent_id))
+        }) {
+            return Err(QuestError::InvalidImport(format!(
+                "quest {} refers to parent quest {}, which is not imported",
+                quest.id,
+                quest.parent_id.unwrap_or_default()
+            )));
+        }
+
+        let mut sorted = Vec::new();
+        let mut placed = HashSet::new();
+        let mut pending: Vec<&QuestRecord> = quests.iter().collect();
+
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<&QuestRecord>, Vec<&QuestRecord>) =
+                pending.into_iter().partition(|quest| {
+                    quest
+                        .parent_id
+                        .is_none_or(|parent_id| placed.contains(&parent_id))
+                });
+
+            // Quests left waiting on each other form a cycle.
+            if ready.is_empty() {
+                return Err(QuestError::InvalidImport(format!(
+                    "quest {} is its own ancestor",
+                    waiting[0].id
+                )));
+            }
+
+            placed.extend(ready.iter().map(|quest| quest.id));
+            sorted.extend(ready);
+            pending = waiting;
+        }
+
+        Ok(sorted)
+    }
+
+    /// Finds the quest in the log with the same objective and creation time, if any. Quests
+    /// without a creation time never conflict.
+    fn find_existing(conn: &Connection, quest: &QuestRecord) -> Result<Option<i64>, QuestError> {
+        let Some(created_at) = quest.created_at else {
+            return Ok(None);
+        };
+
+        let quest_id = conn
+            .query_row(
+                "SELECT id FROM quest
+                WHERE objective = ?1 AND created_at = ?2 AND deleted_at IS NULL
+                ORDER BY id LIMIT 1",
+                params![quest.objective, created_at],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(quest_id)
+    }
+
+    /// Inserts a quest, with its notes, history, and tags, and returns its new identifier. A quest
+    /// without history gets one status change, as if it had just been added.
+    fn insert(
+        conn: &Connection,
+        quest: &QuestRecord,
+        chain_id: Option<i64>,
+    ) -> Result<i64, QuestError> {
+        let tags = quest
+            .tags
+            .iter()
+            .map(|name| tag::parse_name(name))
+            .collect::<Result<Vec<String>, String>>()
+            .map_err(|err| QuestError::InvalidImport(format!("quest {}: {}", quest.id, err)))?;
+
+        conn.execute(
+            "INSERT INTO quest (
+                chain_id, objective, status, tier, due_date, start_date, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                chain_id,
+                quest.objective,
+                quest.status as i64,
+                quest.tier as i64,
+                quest.due,
+                quest.start,
+                quest.created_at,
+                quest.updated_at
+            ],
+        )?;
+        let quest_id = conn.last_insert_rowid();
+
+        if quest.history.is_empty() {
+            let changed_at = quest.updated_at.or(quest.created_at).unwrap_or(Utc::now());
+            conn.execute(
+                "INSERT INTO quest_transition (quest_id, status, changed_at) VALUES (?1, ?2, ?3)",
+                params![quest_id, quest.status as i64, changed_at],
+            )?;
+        }
+
+        for transition in &quest.history {
+            conn.execute(
+                "INSERT INTO quest_transition (quest_id, status, changed_at) VALUES (?1, ?2, ?3)",
+                params![quest_id, transition.status as i64, transition.changed_at],
+            )?;
+        }
+
+        for note in &quest.notes {
+            conn.execute(
+                "INSERT INTO note (quest_id, text, created_at) VALUES (?1, ?2, ?3)",
+                params![quest_id, note.text, note.created_at],
+            )?;
+        }
+
+        for tag in &tags {
+            conn.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", [tag])?;
+            conn.execute(
+                "INSERT OR IGNORE INTO quest_tag (quest_id, tag_id)
+                SELECT ?1, id FROM tag WHERE name = ?2",
+                params![quest_id, tag],
+            )?;
+        }
+
+        Ok(quest_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::Exporter;
+    use crate::quest::{Status, Tier};
+    use crate::testing::{self, at, open, record};
+
+    /// A quest chain whose first quest was created with sub-second precision.
+    fn sample() -> Vec<QuestRecord> {
+        let mut quests = testing::chain();
+        quests[0].created_at = Some(at("2025-01-01T09:30:00.123456Z"));
+        quests
+    }
+
+    fn pending(id: i64, parent_id: Option<i64>) -> QuestRecord {
+        record(id, parent_id, Status::Pending, Tier::Common)
+    }
+
+    fn export(conn: &Connection, format: Format) -> String {
+        let mut output = Vec::new();
+        Exporter::new(conn).export(format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn ids(quests: &[&QuestRecord]) -> Vec<i64> {
+        quests.iter().map(|quest| quest.id).collect()
+    }
+
+    #[test]
+    fn sort_places_parents_before_their_secondary_quests() {
+        let quests = vec![
+            pending(3, Some(2)),
+            pending(2, Some(1)),
+            pending(4, None),
+            pending(1, None),
+        ];
+
+        assert_eq!(ids(&Importer::sort(&quests).unwrap()), [4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn sort_rejects_cycles_missing_parents_and_duplicates() {
+        let cycle = vec![pending(1, None), pending(2, Some(3)), pending(3, Some(2))];
+        let self_parent = vec![pending(1, Some(1))];
+        let missing = vec![pending(1, None), pending(2, Some(5))];
+        let duplicate = vec![pending(1, None), pending(1, None)];
+
+        for quests in [cycle, self_parent, missing, duplicate] {
+            assert!(matches!(
+                Importer::sort(&quests),
+                Err(QuestError::InvalidImport(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn lossless_formats_round_trip() {
+        for format in [Format::Json, Format::Ndjson] {
+            let source = open();
+            Importer::new(&source)
+                .import(&sample(), false, false)
+                .unwrap();
+
+            let (quests, unmapped) =
+                Importer::read(format, export(&source, format).as_bytes()).unwrap();
+            assert!(unmapped.is_empty());
+
+            let target = open();
+            let report = Importer::new(&target)
+                .import(&quests, false, false)
+                .unwrap();
+            assert_eq!(report.imported(), 5);
+            assert_eq!(
+                export(&source, Format::Ndjson),
+                export(&target, Format::Ndjson)
+            );
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_without_notes_or_history() {
+        let source = open();
+        Importer::new(&source)
+            .import(&sample(), false, false)
+            .unwrap();
+
+        let (quests, _) =
+            Importer::read(Format::Csv, export(&source, Format::Csv).as_bytes()).unwrap();
+        assert!(quests.iter().all(|quest| quest.notes.is_empty()));
+
+        let target = open();
+        Importer::new(&target)
+            .import(&quests, false, false)
+            .unwrap();
+        assert_eq!(export(&source, Format::Csv), export(&target, Format::Csv));
+    }
+
+    #[test]
+    fn import_merges_conflicts_only_if_asked() {
+        let conn = open();
+        let importer = Importer::new(&conn);
+        importer.import(&sample(), false, false).unwrap();
+
+        assert!(matches!(
+            importer.import(&sample(), false, false),
+            Err(QuestError::InvalidImport(_))
+        ));
+
+        let report = importer.import(&sample(), true, false).unwrap();
+        assert_eq!((report.imported(), report.merged()), (0, 5));
+    }
+
+    #[test]
+    fn dry_run_leaves_the_log_unchanged() {
+        let conn = open();
+        let report = Importer::new(&conn).import(&sample(), false, true).unwrap();
+
+        assert_eq!(report.imported(), 5);
+        assert_eq!(export(&conn, Format::Ndjson), "");
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::error::QuestError;
+
+/// A timestamped Markdown note in the journal of a quest.
+#[derive(Clone, Debug, Serialize)]
+pub struct Note {
+    created_at: DateTime<Utc>,
+    text: String,
+}
+
+impl Note {
+    /// Copies the creation time.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Borrows the Markdown text.
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+}
+
+/// Stores and loads quest journal notes to and from the database.
+pub struct NoteDao<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> NoteDao<'a> {
+    /// Constructs a new note data access object.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Adds a note to the journal of the specified quest.
+    pub fn add_note(&self, quest_id: i64, text: &str) -> Result<(), QuestError> {
+        self.conn.execute(
+            "INSERT INTO note (quest_id, text, created_at) VALUES (?1, ?2, ?3)",
+            params![quest_id, text, Utc::now()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Gets the journal of the specified quest, oldest note first.
+    pub fn get_notes(&self, quest_id: i64) -> Result<Vec<Note>, QuestError> {
+        let query = "SELECT text, created_at FROM note WHERE quest_id = ?1 ORDER BY created_at, id";
+        let mut stmt = self.conn.prepare(query)?;
+
+        let notes = stmt
+            .query_map([quest_id], |row| {
+                Ok(Note {
+                    text: row.get(0)?,
+                    created_at: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Note>>>()?;
+
+        Ok(notes)
+    }
+}
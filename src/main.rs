@@ -1,13 +1,30 @@
+mod archive;
 mod cli;
 mod database;
+mod date;
+mod doctor;
+mod error;
+mod export;
+mod ical;
+mod import;
+mod log;
+mod migration;
 mod quest;
 mod table;
+mod tag;
+mod taskwarrior;
+#[cfg(test)]
+mod testing;
+mod todotxt;
+mod undo;
+
+use std::process::ExitCode;
 
 use clap::Parser;
 use cli::{Args, Cli};
 
-fn main() {
+fn main() -> ExitCode {
     // Parse and interpret the command line arguments.
     let args = Args::parse();
-    Cli::interpret(args);
+    Cli::interpret(args)
 }
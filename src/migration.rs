@@ -0,0 +1,427 @@
+use rusqlite::Connection;
+
+use crate::error::QuestError;
+
+/// Ordered schema migrations. The schema version stored in `PRAGMA user_version` is the number of
+/// migrations applied so far, so each migration runs exactly once per database. Never edit or
+/// reorder a migration once released; append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: Create the `quest` table. Databases created before migrations were tracked already have
+    // this table, so only create it if it is missing.
+    "CREATE TABLE IF NOT EXISTS quest (
+        id        INTEGER PRIMARY KEY,
+        chain_id  INTEGER,
+        objective TEXT NOT NULL,
+        status    INTEGER NOT NULL,
+        tier      INTEGER NOT NULL,
+        FOREIGN KEY (chain_id) REFERENCES quest(id)
+    )",
+    // 2: Add optional due and start (defer) dates, stored as `YYYY-MM-DD` text.
+    "ALTER TABLE quest ADD COLUMN due_date TEXT;
+    ALTER TABLE quest ADD COLUMN start_date TEXT;",
+    // 3: Add lifecycle timestamps and the status transition history. Quests created before this
+    // migration have no recorded timestamps.
+    "ALTER TABLE quest ADD COLUMN created_at TEXT;
+    ALTER TABLE quest ADD COLUMN updated_at TEXT;
+    CREATE TABLE quest_transition (
+        id         INTEGER PRIMARY KEY,
+        quest_id   INTEGER NOT NULL,
+        status     INTEGER NOT NULL,
+        changed_at TEXT NOT NULL,
+        FOREIGN KEY (quest_id) REFERENCES quest(id) ON DELETE CASCADE
+    );
+    CREATE INDEX quest_transition_quest_id ON quest_transition(quest_id);",
+    // 4: Add tags, which label quests many-to-many.
+    "CREATE TABLE tag (
+        id   INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE quest_tag (
+        quest_id INTEGER NOT NULL,
+        tag_id   INTEGER NOT NULL,
+        PRIMARY KEY (quest_id, tag_id),
+        FOREIGN KEY (quest_id) REFERENCES quest(id) ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES tag(id) ON DELETE CASCADE
+    );
+    CREATE INDEX quest_tag_tag_id ON quest_tag(tag_id);",
+    // 5: Add the quest journal, which holds timestamped Markdown notes.
+    "CREATE TABLE note (
+        id         INTEGER PRIMARY KEY,
+        quest_id   INTEGER NOT NULL,
+        text       TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        FOREIGN KEY (quest_id) REFERENCES quest(id) ON DELETE CASCADE
+    );
+    CREATE INDEX note_quest_id ON note(quest_id);",
+    // 6: Add the undo journal. Each undoable command is recorded as an operation, and while an
+    // operation is recording, the triggers store a JSON image of every row it changes, from before
+    // and after the change. A missing image means the row was inserted or deleted.
+    "CREATE TABLE undo_operation (
+        id          INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        created_at  TEXT NOT NULL,
+        undone_at   TEXT,
+        recording   INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE undo_change (
+        id           INTEGER PRIMARY KEY,
+        operation_id INTEGER NOT NULL,
+        table_name   TEXT NOT NULL,
+        before       TEXT,
+        after        TEXT,
+        FOREIGN KEY (operation_id) REFERENCES undo_operation(id) ON DELETE CASCADE
+    );
+    CREATE INDEX undo_change_operation_id ON undo_change(operation_id);
+    CREATE TRIGGER quest_undo_insert AFTER INSERT ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            NULL,
+            json_object(
+                'id', NEW.id, 'chain_id', NEW.chain_id, 'objective', NEW.objective,
+                'status', NEW.status, 'tier', NEW.tier, 'due_date', NEW.due_date,
+                'start_date', NEW.start_date, 'created_at', NEW.created_at,
+                'updated_at', NEW.updated_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_undo_update AFTER UPDATE ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            json_object(
+                'id', OLD.id, 'chain_id', OLD.chain_id, 'objective', OLD.objective,
+                'status', OLD.status, 'tier', OLD.tier, 'due_date', OLD.due_date,
+                'start_date', OLD.start_date, 'created_at', OLD.created_at,
+                'updated_at', OLD.updated_at
+            ),
+            json_object(
+                'id', NEW.id, 'chain_id', NEW.chain_id, 'objective', NEW.objective,
+                'status', NEW.status, 'tier', NEW.tier, 'due_date', NEW.due_date,
+                'start_date', NEW.start_date, 'created_at', NEW.created_at,
+                'updated_at', NEW.updated_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_undo_delete AFTER DELETE ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            json_object(
+                'id', OLD.id, 'chain_id', OLD.chain_id, 'objective', OLD.objective,
+                'status', OLD.status, 'tier', OLD.tier, 'due_date', OLD.due_date,
+                'start_date', OLD.start_date, 'created_at', OLD.created_at,
+                'updated_at', OLD.updated_at
+            ),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_transition_undo_insert AFTER INSERT ON quest_transition BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_transition',
+            NULL,
+            json_object(
+                'id', NEW.id, 'quest_id', NEW.quest_id, 'status', NEW.status,
+                'changed_at', NEW.changed_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_transition_undo_update AFTER UPDATE ON quest_transition BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_transition',
+            json_object(
+                'id', OLD.id, 'quest_id', OLD.quest_id, 'status', OLD.status,
+                'changed_at', OLD.changed_at
+            ),
+            json_object(
+                'id', NEW.id, 'quest_id', NEW.quest_id, 'status', NEW.status,
+                'changed_at', NEW.changed_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_transition_undo_delete AFTER DELETE ON quest_transition BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_transition',
+            json_object(
+                'id', OLD.id, 'quest_id', OLD.quest_id, 'status', OLD.status,
+                'changed_at', OLD.changed_at
+            ),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER tag_undo_insert AFTER INSERT ON tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'tag',
+            NULL,
+            json_object('id', NEW.id, 'name', NEW.name)
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER tag_undo_update AFTER UPDATE ON tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'tag',
+            json_object('id', OLD.id, 'name', OLD.name),
+            json_object('id', NEW.id, 'name', NEW.name)
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER tag_undo_delete AFTER DELETE ON tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'tag',
+            json_object('id', OLD.id, 'name', OLD.name),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_tag_undo_insert AFTER INSERT ON quest_tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_tag',
+            NULL,
+            json_object('quest_id', NEW.quest_id, 'tag_id', NEW.tag_id)
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_tag_undo_update AFTER UPDATE ON quest_tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_tag',
+            json_object('quest_id', OLD.quest_id, 'tag_id', OLD.tag_id),
+            json_object('quest_id', NEW.quest_id, 'tag_id', NEW.tag_id)
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_tag_undo_delete AFTER DELETE ON quest_tag BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest_tag',
+            json_object('quest_id', OLD.quest_id, 'tag_id', OLD.tag_id),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER note_undo_insert AFTER INSERT ON note BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'note',
+            NULL,
+            json_object(
+                'id', NEW.id, 'quest_id', NEW.quest_id, 'text', NEW.text,
+                'created_at', NEW.created_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER note_undo_update AFTER UPDATE ON note BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'note',
+            json_object(
+                'id', OLD.id, 'quest_id', OLD.quest_id, 'text', OLD.text,
+                'created_at', OLD.created_at
+            ),
+            json_object(
+                'id', NEW.id, 'quest_id', NEW.quest_id, 'text', NEW.text,
+                'created_at', NEW.created_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER note_undo_delete AFTER DELETE ON note BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'note',
+            json_object(
+                'id', OLD.id, 'quest_id', OLD.quest_id, 'text', OLD.text,
+                'created_at', OLD.created_at
+            ),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;",
+    // 7: Add soft deletion. Deleted quests stay in the table, hidden from the log, until they are
+    // restored or purged. The undo triggers of the `quest` table are recreated to record the new
+    // column.
+    "ALTER TABLE quest ADD COLUMN deleted_at TEXT;
+    DROP TRIGGER quest_undo_insert;
+    DROP TRIGGER quest_undo_update;
+    DROP TRIGGER quest_undo_delete;
+    CREATE TRIGGER quest_undo_insert AFTER INSERT ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            NULL,
+            json_object(
+                'id', NEW.id, 'chain_id', NEW.chain_id, 'objective', NEW.objective,
+                'status', NEW.status, 'tier', NEW.tier, 'due_date', NEW.due_date,
+                'start_date', NEW.start_date, 'created_at', NEW.created_at,
+                'updated_at', NEW.updated_at, 'deleted_at', NEW.deleted_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_undo_update AFTER UPDATE ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            json_object(
+                'id', OLD.id, 'chain_id', OLD.chain_id, 'objective', OLD.objective,
+                'status', OLD.status, 'tier', OLD.tier, 'due_date', OLD.due_date,
+                'start_date', OLD.start_date, 'created_at', OLD.created_at,
+                'updated_at', OLD.updated_at, 'deleted_at', OLD.deleted_at
+            ),
+            json_object(
+                'id', NEW.id, 'chain_id', NEW.chain_id, 'objective', NEW.objective,
+                'status', NEW.status, 'tier', NEW.tier, 'due_date', NEW.due_date,
+                'start_date', NEW.start_date, 'created_at', NEW.created_at,
+                'updated_at', NEW.updated_at, 'deleted_at', NEW.deleted_at
+            )
+        FROM undo_operation WHERE recording;
+    END;
+    CREATE TRIGGER quest_undo_delete AFTER DELETE ON quest BEGIN
+        INSERT INTO undo_change (operation_id, table_name, before, after)
+        SELECT
+            id,
+            'quest',
+            json_object(
+                'id', OLD.id, 'chain_id', OLD.chain_id, 'objective', OLD.objective,
+                'status', OLD.status, 'tier', OLD.tier, 'due_date', OLD.due_date,
+                'start_date', OLD.start_date, 'created_at', OLD.created_at,
+                'updated_at', OLD.updated_at, 'deleted_at', OLD.deleted_at
+            ),
+            NULL
+        FROM undo_operation WHERE recording;
+    END;",
+    // 8: Add settings, such as when finished quest chains are archived automatically.
+    "CREATE TABLE setting (
+        name  TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )",
+];
+
+/// Gets the latest schema version known to this build.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+/// Upgrades the database schema to the latest version. Each migration is applied in its own
+/// transaction, together with the version bump, so a failed migration leaves the database at the
+/// last good version. Fails if the database was created by a newer build.
+pub fn migrate(conn: &mut Connection) -> Result<(), QuestError> {
+    let version = user_version(conn)?;
+    if version > latest_version() {
+        return Err(QuestError::StorageFailure(format!(
+            "schema version {} is newer than the supported version {}",
+            version,
+            latest_version()
+        )));
+    }
+
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", idx as i64 + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Reads the schema version of the database.
+fn user_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quest::{QuestDao, Status, Tier};
+
+    #[test]
+    fn upgrades_a_database_created_before_migrations() {
+        // The table created by the first release, which never set the schema version.
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE quest (
+                id        INTEGER PRIMARY KEY,
+                chain_id  INTEGER,
+                objective TEXT NOT NULL,
+                status    INTEGER NOT NULL,
+                tier      INTEGER NOT NULL,
+                FOREIGN KEY (chain_id) REFERENCES quest(id)
+            );",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO quest (id, chain_id, objective, status, tier)
+            VALUES (1, NULL, 'Slay the dragon', ?1, ?2), (2, 1, 'Forge a sword', ?3, ?4)",
+            [
+                Status::Ongoing as i64,
+                Tier::Legendary as i64,
+                Status::Completed as i64,
+                Tier::Common as i64,
+            ],
+        )
+        .unwrap();
+        assert_eq!(user_version(&conn).unwrap(), 0);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+
+        let chains = QuestDao::new(&conn).get_all_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        let main = chains[0].main();
+        assert_eq!(main.objective(), "Slay the dragon");
+        assert_eq!(main.status(), Status::Ongoing);
+        assert_eq!(main.tier(), Tier::Legendary);
+        assert_eq!(main.created_at(), None);
+        assert!(main.tags().is_empty());
+
+        let secondary = chains[0].chains()[0].main();
+        assert_eq!(secondary.id(), 2);
+        assert_eq!(secondary.objective(), "Forge a sword");
+        assert_eq!(secondary.status(), Status::Completed);
+    }
+
+    #[test]
+    fn migrating_twice_changes_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO quest (objective, status, tier) VALUES ('Slay the dragon', 0, 0)",
+            [],
+        )
+        .unwrap();
+        let schema = |conn: &Connection| -> Vec<String> {
+            conn.prepare("SELECT sql FROM sqlite_schema WHERE sql IS NOT NULL ORDER BY name")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<String>>>()
+                .unwrap()
+        };
+        let before = schema(&conn);
+
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert_eq!(schema(&conn), before);
+        let count: i64 = conn
+            .query_row("SELECT COUNT() FROM quest", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -1,17 +1,24 @@
-use std::collections::HashMap;
-use std::convert::From;
-use std::fmt::{Display, Formatter, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
 
+use chrono::{DateTime, NaiveDate, Utc};
 use clap::ValueEnum;
 use colored::{ColoredString, Colorize};
-use rusqlite::{params, Connection};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::database::Transaction;
+use crate::error::QuestError;
 
 /// A collection of quests, containing one main quest and a list of secondary
 /// quest chains.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Chain {
-    chains: Vec<Chain>,
+    #[serde(flatten)]
     main: Quest,
+    #[serde(rename = "quests")]
+    chains: Vec<Chain>,
 }
 
 impl Chain {
@@ -28,14 +35,29 @@ impl Chain {
         &self.chains
     }
 
+    /// Copies the due date.
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.main.due()
+    }
+
+    /// Borrows the main quest.
+    pub fn main(&self) -> &Quest {
+        &self.main
+    }
+
     /// Copies the identifier.
     pub fn id(&self) -> i64 {
         self.main.id()
     }
 
+    /// Checks if the main quest is unfinished and past its due date.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.main.is_overdue(today)
+    }
+
     /// Borrows the objective.
     pub fn objective(&self) -> &String {
-        &self.main.objective()
+        self.main.objective()
     }
 
     /// Copies the status.
@@ -43,6 +65,11 @@ impl Chain {
         self.main.status()
     }
 
+    /// Borrows the tags.
+    pub fn tags(&self) -> &Vec<String> {
+        self.main.tags()
+    }
+
     /// Copies the tier.
     pub fn tier(&self) -> Tier {
         self.main.tier()
@@ -50,13 +77,21 @@ impl Chain {
 }
 
 /// A quest to be completed, including a tier, status, due date, and more.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Quest {
     id: i64,
+    #[serde(rename = "parent_id")]
     chain_id: Option<i64>,
     objective: String,
     status: Status,
     tier: Tier,
+    due: Option<NaiveDate>,
+    start: Option<NaiveDate>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Utc>>,
+    tags: Vec<String>,
 }
 
 /// Quest implementation.
@@ -67,7 +102,44 @@ impl Quest {
 
     /// Constructs a new quest.
     pub fn new(objective: String, status: Status, tier: Tier, chain_id: Option<i64>) -> Self {
-        Self::new_impl(Self::UNINITIALIZED_ID, chain_id, objective, status, tier)
+        Self {
+            id: Self::UNINITIALIZED_ID,
+            chain_id,
+            objective,
+            status,
+            tier,
+            due: None,
+            start: None,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Copies the identifier of the parent quest, if any.
+    pub fn chain_id(&self) -> Option<i64> {
+        self.chain_id
+    }
+
+    /// Copies the creation time, if it was recorded.
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+    }
+
+    /// Copies the deletion time, if the quest is in the trash.
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+
+    /// Copies the due date.
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    /// Borrows a mutable reference to the due date.
+    pub fn due_mut(&mut self) -> &mut Option<NaiveDate> {
+        &mut self.due
     }
 
     // Copies the identifier.
@@ -75,6 +147,12 @@ impl Quest {
         self.id
     }
 
+    /// Checks if the quest is unfinished and past its due date.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        let is_finished = matches!(self.status, Status::Completed | Status::Abandoned);
+        !is_finished && self.due.is_some_and(|due| due < today)
+    }
+
     /// Borrows the objective.
     pub fn objective(&self) -> &String {
         &self.objective
@@ -85,6 +163,16 @@ impl Quest {
         &mut self.objective
     }
 
+    /// Copies the start date.
+    pub fn start(&self) -> Option<NaiveDate> {
+        self.start
+    }
+
+    /// Borrows a mutable reference to the start date.
+    pub fn start_mut(&mut self) -> &mut Option<NaiveDate> {
+        &mut self.start
+    }
+
     /// Copies the status.
     pub fn status(&self) -> Status {
         self.status
@@ -95,6 +183,16 @@ impl Quest {
         &mut self.status
     }
 
+    /// Borrows the tags, sorted by name.
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    /// Borrows a mutable reference to the tags.
+    pub fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
     /// Copies the tier.
     pub fn tier(&self) -> Tier {
         self.tier
@@ -105,22 +203,92 @@ impl Quest {
         &mut self.tier
     }
 
-    /// Constructs a new quest.
-    fn new_impl(
-        id: i64,
-        chain_id: Option<i64>,
-        objective: String,
-        status: Status,
-        tier: Tier,
-    ) -> Self {
+    /// Copies the time of the last modification, if it was recorded.
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
+}
+
+/// A change in the status of a quest, including the status the quest was created with.
+#[derive(Clone, Debug)]
+pub struct Transition {
+    status: Status,
+    changed_at: DateTime<Utc>,
+}
+
+impl Transition {
+    /// Copies the time of the change.
+    pub fn changed_at(&self) -> DateTime<Utc> {
+        self.changed_at
+    }
+
+    /// Copies the new status.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+/// Selects which quests are loaded from the log, and in which order.
+#[derive(Clone, Debug)]
+pub struct QuestFilter {
+    include_deferred: bool,
+    root: Option<i64>,
+    sort: Sort,
+    statuses: Vec<Status>,
+    tags: Vec<String>,
+    tiers: Vec<Tier>,
+    today: NaiveDate,
+}
+
+impl QuestFilter {
+    /// Constructs a filter matching every quest that has started by the specified date.
+    pub fn new(today: NaiveDate) -> Self {
         Self {
-            id,
-            chain_id,
-            objective,
-            status,
-            tier,
+            include_deferred: false,
+            root: None,
+            sort: Sort::Id,
+            statuses: Vec::new(),
+            tags: Vec::new(),
+            tiers: Vec::new(),
+            today,
         }
     }
+
+    /// Borrows a mutable reference to whether quests that have not started are included.
+    pub fn include_deferred_mut(&mut self) -> &mut bool {
+        &mut self.include_deferred
+    }
+
+    /// Copies the root quest.
+    pub fn root(&self) -> Option<i64> {
+        self.root
+    }
+
+    /// Borrows a mutable reference to the root quest, limiting the filter to its chain.
+    pub fn root_mut(&mut self) -> &mut Option<i64> {
+        &mut self.root
+    }
+
+    /// Borrows a mutable reference to the sort order.
+    pub fn sort_mut(&mut self) -> &mut Sort {
+        &mut self.sort
+    }
+
+    /// Borrows a mutable reference to the matching statuses. Empty matches any status.
+    pub fn statuses_mut(&mut self) -> &mut Vec<Status> {
+        &mut self.statuses
+    }
+
+    /// Borrows a mutable reference to the matching tags. A quest matches if it has any of the
+    /// tags. Empty matches any quest.
+    pub fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
+    /// Borrows a mutable reference to the matching tiers. Empty matches any tier.
+    pub fn tiers_mut(&mut self) -> &mut Vec<Tier> {
+        &mut self.tiers
+    }
 }
 
 /// Stores and loads quest data to and from the database.
@@ -129,236 +297,724 @@ pub struct QuestDao<'a> {
 }
 
 impl<'a> QuestDao<'a> {
+    /// The columns read by `quest_from_row`. Tags are aggregated into one space-separated column.
+    const QUEST_COLUMNS: &'static str =
+        "id, chain_id, objective, status, tier, due_date, start_date,
+        created_at, updated_at, deleted_at, (
+            SELECT group_concat(name, ' ') FROM (
+                SELECT tag.name FROM quest_tag
+                INNER JOIN tag ON tag.id = quest_tag.tag_id
+                WHERE quest_tag.quest_id = quest.id
+                ORDER BY tag.name
+            )
+        )";
+
     /// Constructs a new quest data access object.
     pub fn new(conn: &'a Connection) -> Self {
-        // Create the `quest` table if it does not exist.
-        Self::create_table(&conn);
         Self { conn }
     }
 
-    /// Adds a new quest to the database.
-    pub fn add_quest(&self, quest: &Quest) {
+    /// Adds a new quest to the database, records its creation time and initial status, and returns
+    /// the created quest with its identifier. Fails if the parent quest does not exist.
+    pub fn add_quest(&self, quest: &Quest) -> Result<Quest, QuestError> {
+        let now = Utc::now();
+        let tx = Transaction::new(self.conn)?;
+
+        if let Some(chain_id) = quest.chain_id {
+            if !Self::exists(&tx, chain_id)? {
+                return Err(QuestError::InvalidParent(chain_id));
+            }
+        }
+
         let query = "INSERT INTO quest (
             objective,
             status,
             tier,
-            chain_id
-        ) VALUES (?1, ?2, ?3, ?4)";
+            chain_id,
+            due_date,
+            start_date,
+            created_at,
+            updated_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)";
         let params = params![
             quest.objective,
             quest.status as i64,
             quest.tier as i64,
-            quest.chain_id
+            quest.chain_id,
+            quest.due,
+            quest.start,
+            now
         ];
-        self.conn
-            .execute(query, params)
-            .expect("failed to add quest");
-    }
-
-    /// Deletes the specified quest chain from the database.
-    pub fn delete_chain(&self, chain_id: i64) {
-        self.conn
-            .execute(
-                "WITH RECURSIVE chain AS (
-                    SELECT id FROM quest WHERE id = ?1 OR chain_id = ?1
-                    UNION ALL
-                    SELECT quest.id FROM quest
-                    INNER JOIN chain ON quest.chain_id = chain.id
-                ) DELETE FROM quest WHERE id IN (SELECT id FROM chain)",
-                params![chain_id],
-            )
-            .expect("failed to delete quest chain");
+        tx.execute(query, params)
+            .map_err(|err| Self::map_parent_error(err, quest.chain_id))?;
+
+        let quest_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO quest_transition (quest_id, status, changed_at) VALUES (?1, ?2, ?3)",
+            params![quest_id, quest.status as i64, now],
+        )?;
+        Self::save_tags(&tx, quest_id, &quest.tags)?;
+        let quest = self.get_quest(quest_id)?;
+
+        tx.commit()?;
+        Ok(quest)
     }
 
-    /// Gets all quest chains from the database.
-    pub fn get_all_chains(&self) -> Vec<Chain> {
-        // Get all quests from the database.
-        let quests = self.get_all_quests();
+    /// Moves the specified quest chain to the trash, hiding it from the log.
+    pub fn delete_chain(&self, chain_id: i64) -> Result<(), QuestError> {
+        let updated = self.conn.execute(
+            &Self::with_chain("UPDATE quest SET deleted_at = ?2 WHERE id IN chain"),
+            params![chain_id, Utc::now()],
+        )?;
 
-        // Construct a hashmap of chains, where each key is a chain identifer
-        // corresponding to the chain itself and its immediate children. Root
-        // chains will, therefore, be disjoint from chains beyond their
-        // immediate children.
-        let mut disjoint_chains = HashMap::new();
-        let mut root_chains = Vec::new();
+        if updated == 0 {
+            return Err(QuestError::NotFound(chain_id));
+        }
 
-        for quest in quests {
-            let chain = Chain::new(quest);
-            disjoint_chains.insert(chain.main.id, chain.clone());
-
-            if let Some(chain_id) = chain.main.chain_id {
-                // Chain has a parent, which must already exist in the hash map
-                // since the quests are sorted by identifier.
-                let parent_chain = disjoint_chains.get_mut(&chain_id).unwrap();
-                parent_chain.chains.push(chain);
-            } else {
-                // Chain does not have a parent and, thus, must be a root chain.
-                root_chains.push(chain.main.id);
-            }
+        Ok(())
+    }
+
+    /// Gets every quest chain from the database, including finished and deferred quests.
+    pub fn get_all_chains(&self) -> Result<Vec<Chain>, QuestError> {
+        let mut filter = QuestFilter::new(Utc::now().date_naive());
+        filter.include_deferred = true;
+        self.get_chains(&filter)
+    }
+
+    /// Gets the quest chains matching the filter from the database. Every ancestor of a matching
+    /// quest is included, so matching secondary quests are always shown within their chain.
+    pub fn get_chains(&self, filter: &QuestFilter) -> Result<Vec<Chain>, QuestError> {
+        // Conditions a quest must meet to match, in addition to being in scope and visible.
+        let mut conditions = vec!["deleted_at IS NULL".to_owned()];
+
+        if !filter.statuses.is_empty() {
+            let statuses: Vec<String> = filter
+                .statuses
+                .iter()
+                .map(|&status| (status as i64).to_string())
+                .collect();
+            conditions.push(format!("status IN ({})", statuses.join(", ")));
         }
 
-        let mut chains = Vec::new();
+        if !filter.tags.is_empty() {
+            let tags: Vec<String> = (0..filter.tags.len())
+                .map(|idx| format!(":tag{}", idx))
+                .collect();
+            conditions.push(format!(
+                "id IN (
+                    SELECT quest_tag.quest_id FROM quest_tag
+                    INNER JOIN tag ON tag.id = quest_tag.tag_id
+                    WHERE tag.name IN ({})
+                )",
+                tags.join(", ")
+            ));
+        }
 
-        for root_chain in root_chains {
-            let root_chain = &mut disjoint_chains.remove(&root_chain).unwrap();
-            Self::connect(root_chain, &mut disjoint_chains);
-            let chain = root_chain.clone();
-            chains.push(chain);
+        if !filter.tiers.is_empty() {
+            let tiers: Vec<String> = filter
+                .tiers
+                .iter()
+                .map(|&tier| (tier as i64).to_string())
+                .collect();
+            conditions.push(format!("tier IN ({})", tiers.join(", ")));
         }
 
-        chains
-    }
+        // The `scope` holds the subtree of the root quest, if any. The `visible` quests are those
+        // reachable from a main quest without passing through a deferred quest, unless deferred
+        // quests are included. Quests that are not `reachable` from any main quest, such as
+        // orphans and parent cycles, are treated as main quests so bad data is still shown. The
+        // `matched` quests, and all of their ancestors, are selected.
+        let query = format!(
+            "WITH RECURSIVE
+            reachable AS (
+                SELECT id FROM quest WHERE chain_id IS NULL
+                UNION
+                SELECT quest.id FROM quest
+                INNER JOIN reachable ON quest.chain_id = reachable.id
+            ),
+            scope AS (
+                SELECT id FROM quest WHERE id = :root
+                UNION
+                SELECT quest.id FROM quest
+                INNER JOIN scope ON quest.chain_id = scope.id
+            ),
+            visible AS (
+                SELECT id FROM quest
+                WHERE (chain_id IS NULL OR id NOT IN (SELECT id FROM reachable))
+                AND (:deferred OR IFNULL(start_date <= :today, 1))
+                UNION
+                SELECT quest.id FROM quest
+                INNER JOIN visible ON quest.chain_id = visible.id
+                WHERE :deferred OR IFNULL(quest.start_date <= :today, 1)
+            ),
+            matched AS (
+                SELECT id, chain_id FROM quest
+                WHERE (:root IS NULL OR id IN (SELECT id FROM scope))
+                AND id IN (SELECT id FROM visible)
+                AND {}
+            ),
+            ancestor AS (
+                SELECT id, chain_id FROM matched
+                UNION
+                SELECT quest.id, quest.chain_id FROM quest
+                INNER JOIN ancestor ON quest.id = ancestor.chain_id
+                WHERE ancestor.id IS NOT :root
+            )
+            SELECT {} FROM quest
+            WHERE id IN (SELECT id FROM ancestor)
+            ORDER BY {}",
+            conditions.join(" AND "),
+            Self::QUEST_COLUMNS,
+            filter.sort.order_by()
+        );
+        let mut stmt = self.conn.prepare(&query)?;
 
-    /// Gets all quests from the database.
-    pub fn get_all_quests(&self) -> Vec<Quest> {
-        // Prepare the query.
-        let query = "SELECT id, chain_id, objective, status, tier FROM quest ORDER BY id";
-        let mut stmt = self
-            .conn
-            .prepare(query)
-            .expect("failed to prepare get-all-quests statement");
-
-        // Execute the query.
-        let rows = stmt.query_map([], |row| {
-            Ok(Quest::new_impl(
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                Status::from(row.get::<_, i64>(3)?),
-                Tier::from(row.get::<_, i64>(4)?),
-            ))
-        });
-        let quest_iter = rows.expect("failed to get all quests");
-
-        // Extract and return the results.
-        let mut quests = Vec::new();
-        for quest in quest_iter {
-            quests.push(quest.expect("failed to extract quest from query map"));
+        // Bind each tag to its own named parameter.
+        let tag_names: Vec<String> = (0..filter.tags.len())
+            .map(|idx| format!(":tag{}", idx))
+            .collect();
+        let mut params: Vec<(&str, &dyn ToSql)> = vec![
+            (":root", &filter.root),
+            (":deferred", &filter.include_deferred),
+            (":today", &filter.today),
+        ];
+        for (name, tag) in tag_names.iter().zip(&filter.tags) {
+            params.push((name, tag));
         }
 
-        quests
+        let quests = stmt
+            .query_map(params.as_slice(), Self::quest_from_row)?
+            .collect::<rusqlite::Result<Vec<Quest>>>()?;
+
+        Ok(Self::build_chains(quests))
+    }
+
+    /// Gets the ancestors of the specified quest, starting with its main quest and ending with its
+    /// parent. The search is bounded, so a corrupt parent cycle cannot loop forever.
+    pub fn get_ancestors(&self, quest_id: i64) -> Result<Vec<Quest>, QuestError> {
+        let query = format!(
+            "WITH RECURSIVE ancestor AS (
+                SELECT chain_id AS id, 1 AS depth FROM quest WHERE id = ?1
+                UNION
+                SELECT quest.chain_id, ancestor.depth + 1 FROM quest
+                INNER JOIN ancestor ON quest.id = ancestor.id
+                WHERE ancestor.id != ?1 AND ancestor.depth < (SELECT COUNT() FROM quest)
+            )
+            SELECT {} FROM quest
+            WHERE id IN (SELECT id FROM ancestor)
+            ORDER BY (SELECT MAX(depth) FROM ancestor WHERE ancestor.id = quest.id) DESC",
+            Self::QUEST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let ancestors = stmt
+            .query_map([quest_id], Self::quest_from_row)?
+            .collect::<rusqlite::Result<Vec<Quest>>>()?;
+
+        Ok(ancestors)
     }
 
     /// Gets the specified quest from the database.
-    pub fn get_quest(&self, quest_id: i64) -> Quest {
+    pub fn get_quest(&self, quest_id: i64) -> Result<Quest, QuestError> {
         // Prepare the query.
-        let query = "SELECT id, chain_id, objective, status, tier FROM quest WHERE id = ?1";
-        let mut stmt = self
-            .conn
-            .prepare(query)
-            .expect("failed to prepare get-quest statement");
+        let query = format!(
+            "SELECT {} FROM quest WHERE id = ?1 AND deleted_at IS NULL",
+            Self::QUEST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&query)?;
 
         // Execute the query, and return the result.
         let params = [quest_id];
-        stmt.query_row(params, |row: &rusqlite::Row<'_>| {
-            Ok(Quest::new_impl(
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                Status::from(row.get::<_, i64>(3)?),
-                Tier::from(row.get::<_, i64>(4)?),
-            ))
-        })
-        .expect("failed to get quest")
+        stmt.query_row(params, Self::quest_from_row)
+            .optional()?
+            .ok_or(QuestError::NotFound(quest_id))
+    }
+
+    /// Gets the most recently added quest, if any. Quests in the trash are left out.
+    pub fn get_last_quest(&self) -> Result<Option<Quest>, QuestError> {
+        let query = format!(
+            "SELECT {} FROM quest WHERE deleted_at IS NULL
+            ORDER BY created_at DESC, id DESC LIMIT 1",
+            Self::QUEST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let quest = stmt.query_row([], Self::quest_from_row).optional()?;
+
+        Ok(quest)
+    }
+
+    /// Gets the number of secondary quests, direct or nested, of the specified quest by status.
+    /// Statuses without quests are omitted.
+    pub fn get_chain_counts(&self, quest_id: i64) -> Result<Vec<(Status, i64)>, QuestError> {
+        let query = Self::with_chain(
+            "SELECT status, COUNT() FROM quest
+            WHERE id IN chain AND id != ?1
+            GROUP BY status
+            ORDER BY status",
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let counts = stmt
+            .query_map([quest_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(Status, i64)>>>()?;
+
+        Ok(counts)
+    }
+
+    /// Gets the status history of the specified quest, oldest first.
+    pub fn get_history(&self, quest_id: i64) -> Result<Vec<Transition>, QuestError> {
+        let query = "SELECT status, changed_at FROM quest_transition
+            WHERE quest_id = ?1 ORDER BY changed_at, id";
+        let mut stmt = self.conn.prepare(query)?;
+
+        let history = stmt
+            .query_map([quest_id], |row| {
+                Ok(Transition {
+                    status: row.get(0)?,
+                    changed_at: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Transition>>>()?;
+
+        Ok(history)
+    }
+
+    /// Gets the direct secondary quests of the specified quest.
+    pub fn get_secondary_quests(&self, quest_id: i64) -> Result<Vec<Quest>, QuestError> {
+        let query = format!(
+            "SELECT {} FROM quest WHERE chain_id = ?1 AND deleted_at IS NULL ORDER BY id",
+            Self::QUEST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let quests = stmt
+            .query_map([quest_id], Self::quest_from_row)?
+            .collect::<rusqlite::Result<Vec<Quest>>>()?;
+
+        Ok(quests)
+    }
+
+    /// Gets the quest chains in the trash, most recently deleted first, along with the number of
+    /// quests deleted with each. A quest deleted on its own before its parent was deleted is
+    /// listed separately.
+    pub fn get_trash(&self) -> Result<Vec<(Quest, i64)>, QuestError> {
+        let query = format!(
+            "WITH RECURSIVE deletion AS (
+                SELECT id AS root_id, id, deleted_at FROM quest AS root
+                WHERE deleted_at IS NOT NULL AND NOT EXISTS (
+                    SELECT 1 FROM quest AS parent
+                    WHERE parent.id = root.chain_id AND parent.deleted_at = root.deleted_at
+                )
+                UNION
+                SELECT deletion.root_id, quest.id, quest.deleted_at FROM quest
+                INNER JOIN deletion ON quest.chain_id = deletion.id
+                WHERE quest.deleted_at = deletion.deleted_at
+            )
+            SELECT {}, (SELECT COUNT() FROM deletion WHERE root_id = quest.id) FROM quest
+            WHERE id IN (SELECT root_id FROM deletion)
+            ORDER BY deleted_at DESC, id",
+            Self::QUEST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let trash = stmt
+            .query_map([], |row| Ok((Self::quest_from_row(row)?, row.get(11)?)))?
+            .collect::<rusqlite::Result<Vec<(Quest, i64)>>>()?;
+
+        Ok(trash)
     }
 
     // Checks if the specified quest is a main quest.
-    pub fn is_main_quest(&self, quest_id: i64) -> bool {
-        let query = "SELECT COUNT() FROM quest WHERE chain_id = ?1";
-        let mut stmt = self
-            .conn
-            .prepare(query)
-            .expect("failed to prepare get-quest statement");
+    pub fn is_main_quest(&self, quest_id: i64) -> Result<bool, QuestError> {
+        let query = "SELECT COUNT() FROM quest WHERE chain_id = ?1 AND deleted_at IS NULL";
+        let mut stmt = self.conn.prepare(query)?;
 
         let params = [quest_id];
-        stmt.query_row(params, |row: &rusqlite::Row<'_>| Ok(row.get::<_, i64>(0)?))
-            .expect("failed to get quest")
-            > 0
+        let count = stmt.query_row(params, |row: &rusqlite::Row<'_>| row.get::<_, i64>(0))?;
+
+        Ok(count > 0)
     }
 
-    /// Updates the status of every quest in specified quest chain.
-    pub fn update_chain_status(&self, chain_id: i64, status: Status) {
-        self.conn
-            .execute(
-                "WITH RECURSIVE chain AS (
-                    SELECT id FROM quest WHERE id = ?1 OR chain_id = ?1
-                    UNION ALL
-                    SELECT quest.id FROM quest
-                    INNER JOIN chain ON quest.chain_id = chain.id
-                ) UPDATE quest SET status = ?2 WHERE id IN (SELECT id FROM chain)",
-                params![chain_id, status as i64],
+    /// Moves the specified quest, and its secondary quests, under a new parent quest. A quest
+    /// without a parent becomes a main quest. Fails if the parent is the quest itself or one of
+    /// its secondary quests, since the chain would become a cycle.
+    pub fn move_quest(&self, quest_id: i64, chain_id: Option<i64>) -> Result<(), QuestError> {
+        let tx = Transaction::new(self.conn)?;
+
+        if !Self::exists(&tx, quest_id)? {
+            return Err(QuestError::NotFound(quest_id));
+        }
+
+        if let Some(chain_id) = chain_id {
+            let is_descendant = tx
+                .prepare(&Self::with_chain("SELECT 1 FROM chain WHERE id = ?2"))?
+                .exists([quest_id, chain_id])?;
+
+            if is_descendant || !Self::exists(&tx, chain_id)? {
+                return Err(QuestError::InvalidParent(chain_id));
+            }
+        }
+
+        tx.execute(
+            "UPDATE quest SET chain_id = ?2, updated_at = ?3 WHERE id = ?1",
+            params![quest_id, chain_id, Utc::now()],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Permanently deletes the quests moved to the trash before the specified time, or every quest
+    /// in the trash, and returns the number deleted. The undo journal is cleared, since it can no
+    /// longer be replayed once quests are gone.
+    pub fn purge(&self, before: Option<DateTime<Utc>>) -> Result<usize, QuestError> {
+        let tx = Transaction::new(self.conn)?;
+        let purged = "SELECT id FROM quest
+            WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at < ?1)";
+
+        // Delete the status history, tags, and notes of the quests first, so they do not outlive
+        // the quests.
+        tx.execute(
+            &format!("DELETE FROM note WHERE quest_id IN ({})", purged),
+            [before],
+        )?;
+        tx.execute(
+            &format!(
+                "DELETE FROM quest_transition WHERE quest_id IN ({})",
+                purged
+            ),
+            [before],
+        )?;
+        tx.execute(
+            &format!("DELETE FROM quest_tag WHERE quest_id IN ({})", purged),
+            [before],
+        )?;
+
+        let deleted = tx.execute(
+            &format!("DELETE FROM quest WHERE id IN ({})", purged),
+            [before],
+        )?;
+
+        Self::delete_unused_tags(&tx)?;
+        tx.execute("DELETE FROM undo_operation", [])?;
+
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Restores the specified quest from the trash, along with the secondary quests deleted with
+    /// it. Fails if its parent quest is still in the trash.
+    pub fn restore_chain(&self, chain_id: i64) -> Result<(), QuestError> {
+        let tx = Transaction::new(self.conn)?;
+
+        let deleted = tx
+            .query_row(
+                "SELECT chain_id, deleted_at FROM quest WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [chain_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, DateTime<Utc>>(1)?,
+                    ))
+                },
             )
-            .expect("failed to delete quest chain");
+            .optional()?;
+
+        let Some((parent_id, deleted_at)) = deleted else {
+            return Err(QuestError::NotFound(chain_id));
+        };
+
+        if let Some(parent_id) = parent_id {
+            let is_parent_deleted = tx
+                .prepare("SELECT 1 FROM quest WHERE id = ?1 AND deleted_at IS NOT NULL")?
+                .exists([parent_id])?;
+
+            if is_parent_deleted {
+                return Err(QuestError::InvalidParent(parent_id));
+            }
+        }
+
+        tx.execute(
+            "WITH RECURSIVE deletion AS (
+                SELECT id FROM quest WHERE id = ?1
+                UNION
+                SELECT quest.id FROM quest
+                INNER JOIN deletion ON quest.chain_id = deletion.id
+                WHERE quest.deleted_at = ?2
+            ) UPDATE quest SET deleted_at = NULL WHERE id IN deletion",
+            params![chain_id, deleted_at],
+        )?;
+
+        tx.commit()?;
+        Ok(())
     }
 
-    /// Updates the specified quest.
-    pub fn update_quest(&self, quest: &Quest) {
+    /// Updates the status of every quest in specified quest chain.
+    pub fn update_chain_status(&self, chain_id: i64, status: Status) -> Result<(), QuestError> {
+        let now = Utc::now();
+        let tx = Transaction::new(self.conn)?;
+
+        // Record a transition for every quest whose status actually changes.
+        tx.execute(
+            &Self::with_chain(
+                "INSERT INTO quest_transition (quest_id, status, changed_at)
+                SELECT id, ?2, ?3 FROM quest WHERE id IN chain AND status != ?2",
+            ),
+            params![chain_id, status as i64, now],
+        )?;
+
+        let updated = tx.execute(
+            &Self::with_chain(
+                "UPDATE quest SET status = ?2, updated_at = ?3
+                WHERE id IN chain AND status != ?2",
+            ),
+            params![chain_id, status as i64, now],
+        )?;
+
+        if updated == 0 && !Self::exists(&tx, chain_id)? {
+            return Err(QuestError::NotFound(chain_id));
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Updates the specified quest, and records a transition if its status changed. The parent is
+    /// left unchanged; use `move_quest` to change it.
+    pub fn update_quest(&self, quest: &Quest) -> Result<(), QuestError> {
+        let now = Utc::now();
+        let tx = Transaction::new(self.conn)?;
+
+        tx.execute(
+            "INSERT INTO quest_transition (quest_id, status, changed_at)
+            SELECT id, ?2, ?3 FROM quest WHERE id = ?1 AND status != ?2",
+            params![quest.id, quest.status as i64, now],
+        )?;
+
         let query = "UPDATE quest
-        SET chain_id  = ?1,
-            objective = ?2,
-            status    = ?3,
-            tier      = ?4
-        WHERE id = ?5";
+        SET objective  = ?1,
+            status     = ?2,
+            tier       = ?3,
+            due_date   = ?4,
+            start_date = ?5,
+            updated_at = ?6
+        WHERE id = ?7";
         let params = params![
-            quest.chain_id,
             quest.objective,
             quest.status as i64,
             quest.tier as i64,
+            quest.due,
+            quest.start,
+            now,
             quest.id
         ];
-        self.conn
-            .execute(query, params)
-            .expect("failed to update quest");
-    }
-
-    /// Creates the `quest` table if it does not exist.
-    fn create_table(conn: &Connection) {
-        let query = "CREATE TABLE quest (
-                               id        INTEGER PRIMARY KEY,
-                               chain_id  INTEGER,
-                               objective TEXT NOT NULL,
-                               status    INTEGER NOT NULL,
-                               tier      INTEGER NOT NULL,
-                               FOREIGN KEY (chain_id) REFERENCES quest(id)
-                           )";
-        if !Self::exists(&conn) {
-            conn.execute(
-                query,
-                (), // Empty list of parameters.
-            )
-            .expect("failed to create table `quest`");
+        let updated = tx.execute(query, params)?;
+
+        if updated == 0 {
+            return Err(QuestError::NotFound(quest.id));
         }
+
+        Self::save_tags(&tx, quest.id, &quest.tags)?;
+        Self::delete_unused_tags(&tx)?;
+
+        tx.commit()?;
+        Ok(())
     }
 
-    /// Connects a set of disjoint chains into one complete chain, where the
-    /// root chain is the first chain passed into the function.
-    fn connect(chain: &mut Chain, disjoint_chains: &mut HashMap<i64, Chain>) {
-        if chain.chains.is_empty() {
-            return;
+    /// Connects a list of quests into quest chains. A quest is a main quest if it has no parent,
+    /// or if its parent is not in the list. Secondary quests keep the order of the list. Corrupt
+    /// parent cycles are broken at their oldest quest, rather than dropped.
+    fn build_chains(quests: Vec<Quest>) -> Vec<Chain> {
+        let ids: HashSet<i64> = quests.iter().map(|quest| quest.id).collect();
+        let mut secondary_quests: HashMap<i64, Vec<Quest>> = HashMap::new();
+        let mut main_quests = Vec::new();
+
+        for quest in quests {
+            match quest.chain_id {
+                Some(chain_id) if ids.contains(&chain_id) => {
+                    secondary_quests.entry(chain_id).or_default().push(quest);
+                }
+                _ => main_quests.push(quest),
+            }
+        }
+
+        let mut chains: Vec<Chain> = main_quests
+            .into_iter()
+            .map(|quest| Self::connect(quest, &mut secondary_quests))
+            .collect();
+
+        // Any quests left over are part of a parent cycle, which has no main quest.
+        while let Some(quest) = Self::take_oldest(&mut secondary_quests) {
+            chains.push(Self::connect(quest, &mut secondary_quests));
+        }
+
+        chains
+    }
+
+    /// Connects a quest to its secondary quests, recursively. Each secondary quest is removed from
+    /// the map once connected.
+    fn connect(quest: Quest, secondary_quests: &mut HashMap<i64, Vec<Quest>>) -> Chain {
+        let mut chain = Chain::new(quest);
+
+        if let Some(quests) = secondary_quests.remove(&chain.main.id) {
+            chain.chains = quests
+                .into_iter()
+                .map(|quest| Self::connect(quest, secondary_quests))
+                .collect();
+        }
+
+        chain
+    }
+
+    /// Deletes every tag that no longer labels a quest.
+    pub(crate) fn delete_unused_tags(conn: &Connection) -> Result<(), QuestError> {
+        conn.execute(
+            "DELETE FROM tag WHERE id NOT IN (SELECT tag_id FROM quest_tag)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Checks if the specified quest exists.
+    fn exists(conn: &Connection, quest_id: i64) -> Result<bool, QuestError> {
+        let exists = conn
+            .prepare("SELECT 1 FROM quest WHERE id = ?1 AND deleted_at IS NULL")?
+            .exists([quest_id])?;
+
+        Ok(exists)
+    }
+
+    /// Maps a foreign key violation on the `chain_id` column to an invalid parent error.
+    fn map_parent_error(err: rusqlite::Error, chain_id: Option<i64>) -> QuestError {
+        match (err.sqlite_error_code(), chain_id) {
+            (Some(ErrorCode::ConstraintViolation), Some(chain_id)) => {
+                QuestError::InvalidParent(chain_id)
+            }
+            _ => err.into(),
         }
+    }
+
+    /// Constructs a quest from a row of the `id`, `chain_id`, `objective`, `status`, `tier`,
+    /// `due_date`, `start_date`, `created_at`, `updated_at`, `deleted_at`, and aggregated tag
+    /// columns, in that order.
+    fn quest_from_row(row: &Row<'_>) -> rusqlite::Result<Quest> {
+        Ok(Quest {
+            id: row.get(0)?,
+            chain_id: row.get(1)?,
+            objective: row.get(2)?,
+            status: row.get(3)?,
+            tier: row.get(4)?,
+            due: row.get(5)?,
+            start: row.get(6)?,
+            created_at: row.get(7)?,
+            updated_at: row.get(8)?,
+            deleted_at: row.get(9)?,
+            tags: row
+                .get::<_, Option<String>>(10)?
+                .map(|tags| tags.split(' ').map(str::to_owned).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Replaces the tags of the specified quest.
+    fn save_tags(conn: &Connection, quest_id: i64, tags: &[String]) -> Result<(), QuestError> {
+        conn.execute("DELETE FROM quest_tag WHERE quest_id = ?1", [quest_id])?;
 
-        for child_chain in &mut chain.chains {
-            child_chain.chains = disjoint_chains.remove(&child_chain.main.id).unwrap().chains;
-            Self::connect(child_chain, disjoint_chains);
+        for tag in tags {
+            conn.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", [tag])?;
+            conn.execute(
+                "INSERT OR IGNORE INTO quest_tag (quest_id, tag_id)
+                SELECT ?1, id FROM tag WHERE name = ?2",
+                params![quest_id, tag],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes and returns the quest with the smallest identifier from a map of secondary quests.
+    fn take_oldest(secondary_quests: &mut HashMap<i64, Vec<Quest>>) -> Option<Quest> {
+        let (&chain_id, idx) = secondary_quests
+            .iter()
+            .flat_map(|(chain_id, quests)| {
+                quests
+                    .iter()
+                    .enumerate()
+                    .map(move |(idx, quest)| (quest.id, chain_id, idx))
+            })
+            .min()
+            .map(|(_, chain_id, idx)| (chain_id, idx))?;
+
+        let quests = secondary_quests.get_mut(&chain_id)?;
+        let quest = quests.remove(idx);
+        if quests.is_empty() {
+            secondary_quests.remove(&chain_id);
         }
+
+        Some(quest)
     }
 
-    /// Checks if the `quest` table exists.
-    fn exists(conn: &Connection) -> bool {
-        conn.prepare(
-            "SELECT name
-            FROM    sqlite_master
-            WHERE   type = 'table'
-            AND     name = 'quest'",
+    /// Prefixes a statement with the recursive `chain` table, which holds the identifiers of the
+    /// quest `?1` and all of its secondary quests that are not in the trash.
+    fn with_chain(statement: &str) -> String {
+        format!(
+            "WITH RECURSIVE chain AS (
+                SELECT id FROM quest WHERE id = ?1 AND deleted_at IS NULL
+                UNION
+                SELECT quest.id FROM quest
+                INNER JOIN chain ON quest.chain_id = chain.id
+                WHERE quest.deleted_at IS NULL
+            ) {}",
+            statement
         )
-        .expect("failed to prepare check-existence statement")
-        .exists([])
-        .expect("failed to check if table `quest` exists")
     }
 }
 
-/// A quest status, such as pending, ongoing, or completed.
+/// A sort order for quests. Secondary quests are sorted within their chain.
 #[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Sort {
+    /// Oldest quest first
+    Id,
+    /// Earliest due date first, undated quests last
+    Due,
+    /// Pending, ongoing, completed, waiting, then abandoned
+    Status,
+    /// Legendary quests first
+    Tier,
+    /// Most recently modified first
+    Updated,
+}
+
+impl Sort {
+    /// Gets the SQL `ORDER BY` expression for the sort order.
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Due => "due_date IS NULL, due_date, id",
+            Self::Status => "status, id",
+            Self::Tier => "tier DESC, id",
+            Self::Updated => "updated_at IS NULL, updated_at DESC, id",
+        }
+    }
+}
+
+impl Display for Sort {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Id => write!(formatter, "id"),
+            Self::Due => write!(formatter, "due"),
+            Self::Status => write!(formatter, "status"),
+            Self::Tier => write!(formatter, "tier"),
+            Self::Updated => write!(formatter, "updated"),
+        }
+    }
+}
+
+/// A quest status, such as pending, ongoing, or completed.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     Pending = 0,
     Ongoing = 1,
@@ -368,7 +1024,7 @@ pub enum Status {
 }
 
 impl Display for Status {
-    fn fmt(&self, formatter: &mut Formatter) -> Result {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Pending => write!(formatter, "Pending"),
             Self::Ongoing => write!(formatter, "Ongoing"),
@@ -379,21 +1035,33 @@ impl Display for Status {
     }
 }
 
-impl From<i64> for Status {
-    fn from(value: i64) -> Self {
+impl FromSql for Status {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Self::try_from(value.as_i64()?).map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+impl TryFrom<i64> for Status {
+    type Error = QuestError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
         match value {
-            0 => Self::Pending,
-            1 => Self::Ongoing,
-            2 => Self::Completed,
-            3 => Self::Waiting,
-            4 => Self::Abandoned,
-            _ => panic!("unknown status: `{}`", value),
+            0 => Ok(Self::Pending),
+            1 => Ok(Self::Ongoing),
+            2 => Ok(Self::Completed),
+            3 => Ok(Self::Waiting),
+            4 => Ok(Self::Abandoned),
+            _ => Err(QuestError::CorruptValue {
+                column: "status",
+                value,
+            }),
         }
     }
 }
 
 /// A quest tier, indicating its difficulty or importance.
-#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum Tier {
     Common = 0,
     Rare = 1,
@@ -402,7 +1070,7 @@ pub enum Tier {
 }
 
 impl Tier {
-    pub fn to_colored_string(&self) -> ColoredString {
+    pub fn to_colored_string(self) -> ColoredString {
         match self {
             Self::Common => self.to_string().into(),
             Self::Rare => self.to_string().blue(),
@@ -413,7 +1081,7 @@ impl Tier {
 }
 
 impl Display for Tier {
-    fn fmt(&self, formatter: &mut Formatter) -> Result {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Common => write!(formatter, "✦ Common"),
             Self::Rare => write!(formatter, "✦ Rare"),
@@ -423,14 +1091,237 @@ impl Display for Tier {
     }
 }
 
-impl From<i64> for Tier {
-    fn from(value: i64) -> Self {
+impl FromSql for Tier {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Self::try_from(value.as_i64()?).map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+impl TryFrom<i64> for Tier {
+    type Error = QuestError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
         match value {
-            0 => Self::Common,
-            1 => Self::Rare,
-            2 => Self::Epic,
-            3 => Self::Legendary,
-            _ => panic!("unknown tier: `{}`", value),
+            0 => Ok(Self::Common),
+            1 => Ok(Self::Rare),
+            2 => Ok(Self::Epic),
+            3 => Ok(Self::Legendary),
+            _ => Err(QuestError::CorruptValue {
+                column: "tier",
+                value,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::NoteDao;
+    use crate::testing::{add, open};
+
+    /// Lists the quests of chains depth first, as identifiers along with their depth.
+    fn outline(chains: &[Chain]) -> Vec<(i64, usize)> {
+        fn visit(chain: &Chain, depth: usize, outline: &mut Vec<(i64, usize)>) {
+            outline.push((chain.id(), depth));
+            for chain in chain.chains() {
+                visit(chain, depth + 1, outline);
+            }
+        }
+
+        let mut outline = Vec::new();
+        for chain in chains {
+            visit(chain, 0, &mut outline);
+        }
+        outline
+    }
+
+    fn tag(conn: &Connection, quest_id: i64, tag: &str) {
+        let quest_dao = QuestDao::new(conn);
+        let mut quest = quest_dao.get_quest(quest_id).unwrap();
+        quest.tags_mut().push(tag.to_owned());
+        quest_dao.update_quest(&quest).unwrap();
+    }
+
+    fn filter() -> QuestFilter {
+        QuestFilter::new(Utc::now().date_naive())
+    }
+
+    #[test]
+    fn get_chains_shows_the_ancestors_of_matching_quests() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let parent_id = add(&conn, "Forge a sword", Some(main_id));
+        let sibling_id = add(&conn, "Find the lair", Some(main_id));
+        let quest_id = add(&conn, "Mine the ore", Some(parent_id));
+        add(&conn, "Feed the horse", None);
+        tag(&conn, quest_id, "boss");
+        tag(&conn, sibling_id, "lair");
+
+        let mut filter = filter();
+        filter.tags_mut().push("boss".to_owned());
+        let chains = QuestDao::new(&conn).get_chains(&filter).unwrap();
+
+        assert_eq!(
+            outline(&chains),
+            [(main_id, 0), (parent_id, 1), (quest_id, 2)]
+        );
+    }
+
+    #[test]
+    fn get_chains_is_limited_to_the_root_quest() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let root_id = add(&conn, "Forge a sword", Some(main_id));
+        add(&conn, "Find the lair", Some(main_id));
+        let quest_id = add(&conn, "Mine the ore", Some(root_id));
+        let other_id = add(&conn, "Buy the coal", Some(root_id));
+        add(&conn, "Feed the horse", None);
+        let quest_dao = QuestDao::new(&conn);
+
+        let mut filter = filter();
+        *filter.root_mut() = Some(root_id);
+        let chains = quest_dao.get_chains(&filter).unwrap();
+        assert_eq!(
+            outline(&chains),
+            [(root_id, 0), (quest_id, 1), (other_id, 1)]
+        );
+
+        // The ancestors of matching quests stop at the root.
+        tag(&conn, quest_id, "boss");
+        filter.tags_mut().push("boss".to_owned());
+        let chains = quest_dao.get_chains(&filter).unwrap();
+        assert_eq!(outline(&chains), [(root_id, 0), (quest_id, 1)]);
+    }
+
+    #[test]
+    fn get_chains_breaks_parent_cycles_at_their_oldest_quest() {
+        let conn = open();
+        let main_id = add(&conn, "Feed the horse", None);
+        let ids: Vec<i64> = ["Slay the dragon", "Forge a sword", "Mine the ore"]
+            .iter()
+            .map(|objective| add(&conn, objective, None))
+            .collect();
+
+        // Each quest is the parent of the next, and the last is the parent of the first.
+        for (idx, &quest_id) in ids.iter().enumerate() {
+            let parent_id = ids[(idx + ids.len() - 1) % ids.len()];
+            conn.execute(
+                "UPDATE quest SET chain_id = ?2 WHERE id = ?1",
+                [quest_id, parent_id],
+            )
+            .unwrap();
+        }
+
+        let chains = QuestDao::new(&conn).get_all_chains().unwrap();
+        assert_eq!(
+            outline(&chains),
+            [(main_id, 0), (ids[0], 0), (ids[1], 1), (ids[2], 2)]
+        );
+
+        // A match inside the cycle still shows the whole cycle, once.
+        tag(&conn, ids[1], "boss");
+        let mut filter = filter();
+        filter.tags_mut().push("boss".to_owned());
+        let chains = QuestDao::new(&conn).get_chains(&filter).unwrap();
+        assert_eq!(outline(&chains), [(ids[0], 0), (ids[1], 1), (ids[2], 2)]);
+    }
+
+    #[test]
+    fn move_quest_refuses_to_make_a_cycle() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let parent_id = add(&conn, "Forge a sword", Some(main_id));
+        let quest_id = add(&conn, "Mine the ore", Some(parent_id));
+        let quest_dao = QuestDao::new(&conn);
+        let before = quest_dao.get_quest(main_id).unwrap();
+
+        for chain_id in [main_id, quest_id] {
+            let err = quest_dao.move_quest(main_id, Some(chain_id)).unwrap_err();
+            assert!(matches!(err, QuestError::InvalidParent(id) if id == chain_id));
         }
+
+        let after = quest_dao.get_quest(main_id).unwrap();
+        assert_eq!(after.chain_id(), None);
+        assert_eq!(after.updated_at(), before.updated_at());
+        assert_eq!(
+            outline(&quest_dao.get_all_chains().unwrap()),
+            [(main_id, 0), (parent_id, 1), (quest_id, 2)]
+        );
+    }
+
+    #[test]
+    fn restore_chain_needs_the_parent_out_of_the_trash() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let parent_id = add(&conn, "Forge a sword", Some(main_id));
+        let quest_id = add(&conn, "Mine the ore", Some(parent_id));
+        let sibling_id = add(&conn, "Find the lair", Some(main_id));
+        let quest_dao = QuestDao::new(&conn);
+
+        // The sibling is deleted on its own, before the rest of the chain.
+        quest_dao.delete_chain(sibling_id).unwrap();
+        quest_dao.delete_chain(main_id).unwrap();
+
+        let err = quest_dao.restore_chain(parent_id).unwrap_err();
+        assert!(matches!(err, QuestError::InvalidParent(id) if id == main_id));
+        assert!(quest_dao.get_all_chains().unwrap().is_empty());
+
+        // Restoring the main quest brings back the quests deleted with it, but not the sibling.
+        quest_dao.restore_chain(main_id).unwrap();
+        assert_eq!(
+            outline(&quest_dao.get_all_chains().unwrap()),
+            [(main_id, 0), (parent_id, 1), (quest_id, 2)]
+        );
+
+        quest_dao.restore_chain(sibling_id).unwrap();
+        assert_eq!(quest_dao.get_all_chains().unwrap()[0].chains().len(), 2);
+        assert!(matches!(
+            quest_dao.restore_chain(sibling_id),
+            Err(QuestError::NotFound(id)) if id == sibling_id
+        ));
+    }
+
+    #[test]
+    fn purge_deletes_everything_that_refers_to_the_quests() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let quest_id = add(&conn, "Forge a sword", Some(main_id));
+        let kept_id = add(&conn, "Feed the horse", None);
+        let recent_id = add(&conn, "Find the lair", None);
+        tag(&conn, quest_id, "boss");
+        tag(&conn, quest_id, "forge");
+        tag(&conn, kept_id, "boss");
+        NoteDao::new(&conn)
+            .add_note(quest_id, "Bring fire resistance.")
+            .unwrap();
+        let quest_dao = QuestDao::new(&conn);
+
+        quest_dao.delete_chain(main_id).unwrap();
+        let before = Utc::now();
+        quest_dao.delete_chain(recent_id).unwrap();
+
+        assert_eq!(quest_dao.purge(Some(before)).unwrap(), 2);
+
+        let count = |query: &str| -> i64 { conn.query_row(query, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT() FROM note"), 0);
+        assert_eq!(count("SELECT COUNT() FROM quest_tag"), 1);
+        assert_eq!(count("SELECT COUNT() FROM undo_operation"), 0);
+
+        // Only tags still in use are kept.
+        assert_eq!(count("SELECT COUNT() FROM tag"), 1);
+        assert_eq!(
+            quest_dao.get_quest(kept_id).unwrap().tags(),
+            &vec!["boss".to_owned()]
+        );
+        assert!(quest_dao.get_history(main_id).unwrap().is_empty());
+        assert!(quest_dao.get_history(quest_id).unwrap().is_empty());
+
+        // The quest deleted afterwards is still in the trash.
+        let trash = quest_dao.get_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].0.id(), recent_id);
+        assert_eq!(quest_dao.purge(None).unwrap(), 1);
+        assert_eq!(count("SELECT COUNT() FROM quest_transition"), 1);
     }
 }
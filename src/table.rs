@@ -1,12 +1,16 @@
 use std::convert::From;
+use std::{io, mem};
 
 use colored::ColoredString;
+use terminal_size::Width;
 
 use crate::quest::{Status, Tier};
 
-/// A table cell. Supports plain or rich text.
+/// A table cell. Supports plain or rich text, after an optional plain prefix.
 pub struct Cell {
     content: ColoredString,
+    prefix: String,
+    continuation: String,
 }
 
 impl Cell {
@@ -15,61 +19,129 @@ impl Cell {
     }
 
     pub fn width(&self) -> usize {
-        self.content.chars().count()
+        self.prefix.chars().count() + self.content.chars().count()
+    }
+
+    /// Prepends a prefix to the cell. Lines wrapped onto the next line start with the continuation
+    /// instead, which should be as wide as the prefix.
+    pub fn with_prefix(mut self, prefix: String, continuation: String) -> Self {
+        self.prefix = prefix;
+        self.continuation = continuation;
+        self
+    }
+
+    /// Formats the cell as lines of at most the specified width, wrapping the content at
+    /// whitespace, or within words longer than a line. Returns each line with its width.
+    fn lines(&self, width: usize) -> Vec<(String, usize)> {
+        if self.width() <= width {
+            let line = format!("{}{}", self.prefix, self.content);
+            return vec![(line, self.width())];
+        }
+
+        let prefix_width = self.prefix.chars().count();
+        let text_width = width.saturating_sub(prefix_width).max(1);
+
+        Self::wrap(&self.content.input, text_width)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                let prefix = if idx == 0 {
+                    &self.prefix
+                } else {
+                    &self.continuation
+                };
+                let mut line = self.content.clone();
+                line.input = text;
+                let width = prefix_width + line.chars().count();
+                (format!("{}{}", prefix, line), width)
+            })
+            .collect()
+    }
+
+    /// Wraps text into lines of at most the specified width.
+    fn wrap(text: &str, width: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+
+        for mut word in text.split_whitespace() {
+            loop {
+                let line_width = line.chars().count();
+                let separator_width = if line.is_empty() { 0 } else { 1 };
+
+                if line_width + separator_width + word.chars().count() <= width {
+                    if !line.is_empty() {
+                        line.push(' ');
+                    }
+                    line.push_str(word);
+                    break;
+                }
+
+                if !line.is_empty() {
+                    lines.push(mem::take(&mut line));
+                    continue;
+                }
+
+                // Break a word that is longer than a whole line.
+                let end = word
+                    .char_indices()
+                    .nth(width)
+                    .map_or(word.len(), |(idx, _)| idx);
+                lines.push(word[..end].to_owned());
+                word = &word[end..];
+            }
+        }
+
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+
+        lines
     }
 }
 
 impl From<ColoredString> for Cell {
     fn from(value: ColoredString) -> Self {
-        Self { content: value }
+        Self {
+            content: value,
+            prefix: String::new(),
+            continuation: String::new(),
+        }
     }
 }
 
 impl From<i64> for Cell {
     fn from(value: i64) -> Self {
-        Self {
-            content: value.to_string().into(),
-        }
+        Self::from(ColoredString::from(value.to_string()))
     }
 }
 
 impl From<Status> for Cell {
     fn from(value: Status) -> Self {
-        Self {
-            content: value.to_string().into(),
-        }
+        Self::from(ColoredString::from(value.to_string()))
     }
 }
 
 impl From<&String> for Cell {
     fn from(value: &String) -> Self {
-        Self {
-            content: value.clone().into(),
-        }
+        Self::from(ColoredString::from(value.clone()))
     }
 }
 
 impl From<String> for Cell {
     fn from(value: String) -> Self {
-        Self {
-            content: value.into(),
-        }
+        Self::from(ColoredString::from(value))
     }
 }
 
 impl From<&str> for Cell {
     fn from(value: &str) -> Self {
-        Self {
-            content: value.to_owned().into(),
-        }
+        Self::from(ColoredString::from(value))
     }
 }
 
 impl From<Tier> for Cell {
     fn from(value: Tier) -> Self {
-        Self {
-            content: value.to_colored_string(),
-        }
+        Self::from(value.to_colored_string())
     }
 }
 
@@ -78,9 +150,14 @@ pub struct Table {
     column_widths: Vec<usize>,
     columns: Vec<Cell>,
     rows: Vec<Vec<Cell>>,
+    wrapped_column: Option<usize>,
+    max_width: Option<usize>,
 }
 
 impl Table {
+    /// The narrowest a wrapped column gets, besides the prefixes of its cells.
+    const MIN_WRAP_WIDTH: usize = 10;
+
     /// Constructs a new table.
     pub fn new(columns: Vec<Cell>) -> Self {
         let mut column_widths = Vec::new();
@@ -92,6 +169,8 @@ impl Table {
             column_widths,
             columns,
             rows: Vec::new(),
+            wrapped_column: None,
+            max_width: None,
         }
     }
 
@@ -107,28 +186,158 @@ impl Table {
         self.rows.push(row);
     }
 
+    /// Shrinks a column, wrapping its cells, whenever the table is wider than the maximum width.
+    /// Other columns keep their width. No maximum width leaves the table as wide as it needs.
+    pub fn wrap(&mut self, column_idx: usize, max_width: Option<usize>) {
+        self.wrapped_column = Some(column_idx);
+        self.max_width = max_width;
+    }
+
     /// Formats and prints the table to the standard output.
     pub fn show(&self) {
+        let column_widths = self.fit_column_widths();
         let mut table = String::new();
 
         // Format the column headers.
         for (column_idx, column) in self.columns.iter().enumerate() {
-            let column_width = self.column_widths[column_idx];
+            let column_width = column_widths[column_idx];
             let column = format!("{:1$} ", column.content(), column_width);
             table.push_str(column.as_str());
         }
 
-        // Format the rows.
+        // Format the rows, each of which may span several lines once wrapped.
         for row in &self.rows {
-            table.push_str("\n");
-            for (column_idx, cell) in row.iter().enumerate() {
-                let column_width = self.column_widths[column_idx];
-                let cell = format!("{:1$} ", cell.content(), column_width);
-                table.push_str(cell.as_str());
+            let cells: Vec<Vec<(String, usize)>> = row
+                .iter()
+                .zip(&column_widths)
+                .map(|(cell, &column_width)| cell.lines(column_width))
+                .collect();
+            let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+            for line_idx in 0..height {
+                table.push('\n');
+                for (column_idx, lines) in cells.iter().enumerate() {
+                    let (line, width) = lines
+                        .get(line_idx)
+                        .map_or(("", 0), |(line, width)| (line.as_str(), *width));
+                    let padding = column_widths[column_idx].saturating_sub(width);
+                    table.push_str(&format!("{}{} ", line, " ".repeat(padding)));
+                }
             }
         }
 
         // Print the table.
         println!("{}", table);
     }
+
+    /// Gets the column widths, after shrinking the wrapped column to fit the maximum width. The
+    /// column never gets narrower than its header, or than the prefixes of its cells leave room for.
+    fn fit_column_widths(&self) -> Vec<usize> {
+        let mut column_widths = self.column_widths.clone();
+        let (Some(column_idx), Some(max_width)) = (self.wrapped_column, self.max_width) else {
+            return column_widths;
+        };
+
+        // Every cell is followed by a space.
+        let table_width: usize = column_widths.iter().map(|width| width + 1).sum();
+        if table_width <= max_width {
+            return column_widths;
+        }
+
+        let min_width = self
+            .rows
+            .iter()
+            .map(|row| row[column_idx].prefix.chars().count() + Self::MIN_WRAP_WIDTH)
+            .chain([self.columns[column_idx].width()])
+            .max()
+            .unwrap_or_default()
+            .min(column_widths[column_idx]);
+        column_widths[column_idx] = column_widths[column_idx]
+            .saturating_sub(table_width - max_width)
+            .max(min_width);
+
+        column_widths
+    }
+}
+
+/// Gets the width of the terminal, if the standard output is one.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size_of(io::stdout()).map(|(Width(width), _)| width as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_whitespace() {
+        assert_eq!(
+            Cell::wrap("Slay the  dragon\tof the north", 10),
+            ["Slay the", "dragon of", "the north"]
+        );
+        assert_eq!(Cell::wrap("exactly10!", 10), ["exactly10!"]);
+        assert_eq!(Cell::wrap("", 10), [""]);
+        assert_eq!(Cell::wrap("   ", 10), [""]);
+    }
+
+    #[test]
+    fn breaks_words_longer_than_a_line() {
+        assert_eq!(
+            Cell::wrap("a abcdefghijkl b", 5),
+            ["a", "abcde", "fghij", "kl b"]
+        );
+        assert_eq!(Cell::wrap("abc", 1), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn counts_characters_rather_than_bytes() {
+        assert_eq!(Cell::wrap("ééé ééé", 7), ["ééé ééé"]);
+        assert_eq!(Cell::wrap("drachenkönigin", 7), ["drachen", "königin"]);
+    }
+
+    #[test]
+    fn continues_wrapped_lines_under_the_prefix() {
+        let cell = Cell::from("Slay the dragon").with_prefix("├── ".to_owned(), "│   ".to_owned());
+
+        assert_eq!(
+            cell.lines(13),
+            [
+                ("├── Slay the".to_owned(), 12),
+                ("│   dragon".to_owned(), 10)
+            ]
+        );
+        assert_eq!(cell.lines(19), [("├── Slay the dragon".to_owned(), 19)]);
+    }
+
+    fn table(max_width: Option<usize>) -> Table {
+        let mut table = Table::new(vec![Cell::from("ID"), Cell::from("Objective")]);
+        table.add(vec![
+            Cell::from(1),
+            Cell::from("Slay the dragon of the north"),
+        ]);
+        table.add(vec![
+            Cell::from(2),
+            Cell::from("Forge").with_prefix("└── ".to_owned(), "    ".to_owned()),
+        ]);
+        table.wrap(1, max_width);
+        table
+    }
+
+    #[test]
+    fn shrinks_the_wrapped_column_to_fit() {
+        assert_eq!(table(None).fit_column_widths(), [2, 28]);
+        assert_eq!(table(Some(80)).fit_column_widths(), [2, 28]);
+        assert_eq!(table(Some(20)).fit_column_widths(), [2, 16]);
+    }
+
+    #[test]
+    fn keeps_the_wrapped_column_wide_enough_for_prefixes_and_headers() {
+        // The prefix of the second row needs 4 columns, besides the minimum of 10.
+        assert_eq!(table(Some(5)).fit_column_widths(), [2, 14]);
+
+        let mut table = Table::new(vec![Cell::from("ID"), Cell::from("Objective")]);
+        table.add(vec![Cell::from(1), Cell::from("Slay")]);
+        table.wrap(1, Some(5));
+        assert_eq!(table.fit_column_widths(), [2, 9]);
+    }
 }
@@ -0,0 +1,95 @@
+use rusqlite::Connection;
+
+use crate::error::QuestError;
+use crate::quest::Status;
+
+/// A tag labelling quests, with the number of open and closed quests it labels.
+#[derive(Clone, Debug)]
+pub struct Tag {
+    name: String,
+    open: i64,
+    closed: i64,
+}
+
+impl Tag {
+    /// Copies the number of completed or abandoned quests.
+    pub fn closed(&self) -> i64 {
+        self.closed
+    }
+
+    /// Borrows the name.
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// Copies the number of pending, ongoing, or waiting quests.
+    pub fn open(&self) -> i64 {
+        self.open
+    }
+}
+
+/// Loads tag data from the database. Tags are created and assigned through the `QuestDao`.
+pub struct TagDao<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> TagDao<'a> {
+    /// Constructs a new tag data access object.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Gets all tags from the database, sorted by name.
+    pub fn get_all_tags(&self) -> Result<Vec<Tag>, QuestError> {
+        let query = "SELECT tag.name,
+                SUM(quest.status NOT IN (?1, ?2)),
+                SUM(quest.status IN (?1, ?2))
+            FROM tag
+            INNER JOIN quest_tag ON quest_tag.tag_id = tag.id
+            INNER JOIN quest ON quest.id = quest_tag.quest_id
+            WHERE quest.deleted_at IS NULL
+            GROUP BY tag.id
+            ORDER BY tag.name";
+        let mut stmt = self.conn.prepare(query)?;
+
+        let tags = stmt
+            .query_map(
+                [Status::Completed as i64, Status::Abandoned as i64],
+                |row| {
+                    Ok(Tag {
+                        name: row.get(0)?,
+                        open: row.get(1)?,
+                        closed: row.get(2)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<Tag>>>()?;
+
+        Ok(tags)
+    }
+}
+
+/// Parses a `+tag` command line argument into a tag name.
+pub fn parse_tag(input: &str) -> Result<String, String> {
+    match input.strip_prefix('+') {
+        Some(name) => parse_name(name),
+        None => Err(format!("tags must start with `+`, e.g. `+{}`", input)),
+    }
+}
+
+/// Parses a tag name, with or without a leading `+`. Names are case-insensitive, and may not
+/// contain whitespace or commas.
+pub fn parse_name(input: &str) -> Result<String, String> {
+    let name = input.strip_prefix('+').unwrap_or(input).to_lowercase();
+
+    if name.is_empty() {
+        Err("tag names may not be empty".to_owned())
+    } else if name.contains(|c: char| c.is_whitespace() || c == ',') {
+        Err(format!(
+            "tag `{}` may not contain whitespace or commas",
+            name
+        ))
+    } else {
+        Ok(name)
+    }
+}
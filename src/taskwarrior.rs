@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{Map, Value};
+
+use crate::date;
+use crate::error::QuestError;
+use crate::export::{NoteRecord, QuestRecord, TransitionRecord};
+use crate::quest::{Status, Tier};
+
+/// The format of Taskwarrior timestamps.
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The task attributes that are mapped onto quests, or that need no mapping.
+const MAPPED_ATTRIBUTES: &[&str] = &[
+    "annotations",
+    "depends",
+    "description",
+    "due",
+    "end",
+    "entry",
+    "id",
+    "modified",
+    "parent",
+    "priority",
+    "project",
+    "scheduled",
+    "start",
+    "status",
+    "tags",
+    "urgency",
+    "uuid",
+    "wait",
+];
+
+/// Reads the JSON written by `task export` as quests, along with a description of everything that
+/// could not be mapped. A task that depends on other tasks becomes their parent quest, and a
+/// recurring task becomes the parent of its instances. Projects become tags, annotations become
+/// notes, and the wait date, or else the scheduled date, becomes the start date.
+pub fn read(reader: impl Read) -> Result<(Vec<QuestRecord>, Vec<String>), QuestError> {
+    let tasks: Vec<Map<String, Value>> = serde_json::from_reader(reader)
+        .map_err(|err| QuestError::InvalidImport(err.to_string()))?;
+
+    // Maps the UUID of each task to its identifier, which is its position.
+    let quest_ids: HashMap<&str, i64> = tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, task)| Some((task.get("uuid")?.as_str()?, idx as i64 + 1)))
+        .collect();
+
+    let mut quests = Vec::new();
+    let mut unmapped = Vec::new();
+
+    for (idx, task) in tasks.iter().enumerate() {
+        let mut task_unmapped = Vec::new();
+        let quest = to_record(idx as i64 + 1, task, &mut task_unmapped)?;
+
+        task_unmapped.extend(
+            task.keys()
+                .filter(|key| !MAPPED_ATTRIBUTES.contains(&key.as_str()))
+                .cloned(),
+        );
+        if !task_unmapped.is_empty() {
+            unmapped.push(format!("{}: {}", describe(task), task_unmapped.join(", ")));
+        }
+
+        quests.push(quest);
+    }
+
+    // Link each task to the task depending on it, or else to the recurring task it belongs to.
+    let mut parent_ids: HashMap<i64, i64> = HashMap::new();
+    for (idx, task) in tasks.iter().enumerate() {
+        let parent_id = idx as i64 + 1;
+
+        for uuid in depends(task) {
+            match quest_ids.get(uuid.as_str()) {
+                Some(&quest_id) if !parent_ids.contains_key(&quest_id) => {
+                    parent_ids.insert(quest_id, parent_id);
+                }
+                Some(_) => unmapped.push(format!(
+                    "{}: depends on {}, which is already a secondary quest",
+                    describe(task),
+                    uuid
+                )),
+                None => unmapped.push(format!(
+                    "{}: depends on {}, which is not imported",
+                    describe(task),
+                    uuid
+                )),
+            }
+        }
+    }
+
+    for (idx, task) in tasks.iter().enumerate() {
+        let quest_id = idx as i64 + 1;
+        let Some(uuid) = task.get("parent").and_then(Value::as_str) else {
+            continue;
+        };
+
+        match quest_ids.get(uuid) {
+            Some(&parent_id) if !parent_ids.contains_key(&quest_id) => {
+                parent_ids.insert(quest_id, parent_id);
+            }
+            Some(_) => unmapped.push(format!(
+                "{}: parent {}, since a dependency already holds it",
+                describe(task),
+                uuid
+            )),
+            None => unmapped.push(format!(
+                "{}: parent {}, which is not imported",
+                describe(task),
+                uuid
+            )),
+        }
+    }
+
+    for quest in &mut quests {
+        quest.parent_id = parent_ids.get(&quest.id).copied();
+    }
+
+    Ok((quests, unmapped))
+}
+
+/// Converts a task into a quest, without its parent, and adds the attributes that could not be
+/// mapped to the list.
+fn to_record(
+    id: i64,
+    task: &Map<String, Value>,
+    unmapped: &mut Vec<String>,
+) -> Result<QuestRecord, QuestError> {
+    let text = |key: &str| task.get(key).and_then(Value::as_str);
+    let datetime = |key: &str| text(key).map(parse_datetime).transpose();
+
+    let objective = text("description")
+        .filter(|description| !description.trim().is_empty())
+        .ok_or_else(|| {
+            QuestError::InvalidImport(format!("task {} has no description", describe(task)))
+        })?
+        .to_owned();
+
+    let created_at = datetime("entry")?;
+    let modified_at = datetime("modified")?.or(created_at);
+    let started_at = datetime("start")?;
+    let ended_at = datetime("end")?;
+
+    let status = match text("status").unwrap_or("pending") {
+        "completed" => Status::Completed,
+        "deleted" => Status::Abandoned,
+        "waiting" => Status::Waiting,
+        _ if started_at.is_some() => Status::Ongoing,
+        _ => Status::Pending,
+    };
+
+    let tier = match text("priority") {
+        Some("H") => Tier::Legendary,
+        Some("M") => Tier::Epic,
+        Some("L") => Tier::Rare,
+        Some(priority) => {
+            unmapped.push(format!("priority {}", priority));
+            Tier::Common
+        }
+        None => Tier::Common,
+    };
+
+    let start = match (datetime("wait")?, datetime("scheduled")?) {
+        (Some(wait), Some(_)) => {
+            unmapped.push("scheduled".to_owned());
+            Some(wait)
+        }
+        (wait, scheduled) => wait.or(scheduled),
+    };
+
+    // Tag names may not contain whitespace or commas, which projects such as `Home Stuff` may.
+    let tags: Vec<String> = task
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .chain(text("project"))
+        .map(|tag| {
+            tag.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<&str>>()
+                .join("-")
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    let notes = task
+        .get("annotations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|annotation| {
+            let text = annotation.get("description").and_then(Value::as_str);
+            let created_at = annotation.get("entry").and_then(Value::as_str);
+            Ok(NoteRecord {
+                created_at: created_at
+                    .map(parse_datetime)
+                    .transpose()?
+                    .unwrap_or(Utc::now()),
+                text: text.unwrap_or_default().to_owned(),
+            })
+        })
+        .collect::<Result<Vec<NoteRecord>, QuestError>>()?;
+
+    // Rebuild the history from when the task was entered, started, and ended.
+    let mut history = Vec::new();
+    if let Some(created_at) = created_at {
+        history.push(TransitionRecord {
+            status: Status::Pending,
+            changed_at: created_at,
+        });
+    }
+    if let Some(started_at) = started_at {
+        history.push(TransitionRecord {
+            status: Status::Ongoing,
+            changed_at: started_at,
+        });
+    }
+    if let (Some(ended_at), Status::Completed | Status::Abandoned) = (ended_at, status) {
+        history.push(TransitionRecord {
+            status,
+            changed_at: ended_at,
+        });
+    }
+    if history
+        .last()
+        .is_some_and(|transition| transition.status != status)
+    {
+        history.push(TransitionRecord {
+            status,
+            changed_at: modified_at.unwrap_or(Utc::now()),
+        });
+    }
+
+    Ok(QuestRecord {
+        id,
+        parent_id: None,
+        objective,
+        status,
+        tier,
+        due: datetime("due")?.map(date::local_date),
+        start: start.map(date::local_date),
+        created_at,
+        updated_at: modified_at,
+        tags,
+        notes,
+        history,
+        quests: None,
+    })
+}
+
+/// Gets the UUIDs of the tasks a task depends on, written as a list, or as one comma-separated
+/// string by older versions of Taskwarrior.
+fn depends(task: &Map<String, Value>) -> Vec<String> {
+    match task.get("depends") {
+        Some(Value::Array(uuids)) => uuids
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+        Some(Value::String(uuids)) => uuids
+            .split(',')
+            .filter(|uuid| !uuid.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Describes a task by its UUID and description, for the report.
+fn describe(task: &Map<String, Value>) -> String {
+    let uuid = task.get("uuid").and_then(Value::as_str).unwrap_or("?");
+    let description = task
+        .get("description")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    format!("task {} `{}`", uuid, description)
+}
+
+/// Parses a Taskwarrior timestamp.
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>, QuestError> {
+    NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .map(|datetime| datetime.and_utc())
+        .map_err(|_| QuestError::InvalidImport(format!("invalid timestamp `{}`", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::testing::at;
+
+    fn read_str(json: &str) -> (Vec<QuestRecord>, Vec<String>) {
+        read(json.as_bytes()).unwrap()
+    }
+
+    fn parents(quests: &[QuestRecord]) -> Vec<Option<i64>> {
+        quests.iter().map(|quest| quest.parent_id).collect()
+    }
+
+    #[test]
+    fn depending_tasks_become_parents() {
+        let (quests, unmapped) = read_str(
+            r#"[
+                {"uuid": "a", "description": "Slay the dragon", "depends": ["b", "c"]},
+                {"uuid": "b", "description": "Forge a sword"},
+                {"uuid": "c", "description": "Find the lair", "depends": "d"},
+                {"uuid": "d", "description": "Ask around"}
+            ]"#,
+        );
+
+        assert_eq!(parents(&quests), [None, Some(1), Some(1), Some(3)]);
+        assert!(unmapped.is_empty());
+    }
+
+    #[test]
+    fn recurring_instances_become_secondary_quests() {
+        let (quests, unmapped) = read_str(
+            r#"[
+                {"uuid": "a", "description": "Feed the horse", "status": "recurring"},
+                {"uuid": "b", "description": "Feed the horse", "parent": "a"},
+                {"uuid": "c", "description": "Feed the horse", "parent": "a"}
+            ]"#,
+        );
+
+        assert_eq!(parents(&quests), [None, Some(1), Some(1)]);
+        assert!(unmapped.is_empty());
+    }
+
+    #[test]
+    fn reports_dependencies_that_cannot_be_linked() {
+        let (quests, unmapped) = read_str(
+            r#"[
+                {"uuid": "a", "description": "Slay the dragon", "depends": ["c"]},
+                {"uuid": "b", "description": "Slay the hydra", "depends": ["c", "x"]},
+                {"uuid": "c", "description": "Forge a sword"},
+                {"uuid": "d", "description": "Feed the horse", "parent": "y"}
+            ]"#,
+        );
+
+        assert_eq!(parents(&quests), [None, None, Some(1), None]);
+        assert_eq!(
+            unmapped,
+            [
+                "task b `Slay the hydra`: depends on c, which is already a secondary quest",
+                "task b `Slay the hydra`: depends on x, which is not imported",
+                "task d `Feed the horse`: parent y, which is not imported",
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_statuses_priorities_and_dates() {
+        let (quests, unmapped) = read_str(
+            r#"[
+                {"description": "Pending"},
+                {"description": "Started", "start": "20250102T120000Z", "priority": "H"},
+                {"description": "Waiting", "status": "waiting", "priority": "M",
+                    "wait": "20250301T120000Z", "scheduled": "20250201T120000Z"},
+                {"description": "Completed", "status": "completed", "priority": "L",
+                    "entry": "20250101T120000Z", "end": "20250105T120000Z"},
+                {"description": "Deleted", "status": "deleted", "priority": "X",
+                    "scheduled": "20250201T120000Z", "due": "20250314T120000Z"}
+            ]"#,
+        );
+
+        let statuses: Vec<Status> = quests.iter().map(|quest| quest.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                Status::Pending,
+                Status::Ongoing,
+                Status::Waiting,
+                Status::Completed,
+                Status::Abandoned,
+            ]
+        );
+
+        let tiers: Vec<Tier> = quests.iter().map(|quest| quest.tier).collect();
+        assert_eq!(
+            tiers,
+            [
+                Tier::Common,
+                Tier::Legendary,
+                Tier::Epic,
+                Tier::Rare,
+                Tier::Common,
+            ]
+        );
+
+        // The wait date wins over the scheduled date.
+        assert_eq!(quests[2].start, NaiveDate::from_ymd_opt(2025, 3, 1));
+        assert_eq!(quests[4].start, NaiveDate::from_ymd_opt(2025, 2, 1));
+        assert_eq!(quests[4].due, NaiveDate::from_ymd_opt(2025, 3, 14));
+
+        let history: Vec<(Status, DateTime<Utc>)> = quests[3]
+            .history
+            .iter()
+            .map(|transition| (transition.status, transition.changed_at))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (Status::Pending, at("2025-01-01T12:00:00Z")),
+                (Status::Completed, at("2025-01-05T12:00:00Z")),
+            ]
+        );
+
+        assert_eq!(
+            unmapped,
+            [
+                "task ? `Waiting`: scheduled",
+                "task ? `Deleted`: priority X",
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_projects_to_tags_and_annotations_to_notes() {
+        let (quests, _) = read_str(
+            r#"[{
+                "description": "Slay the dragon",
+                "tags": ["boss"],
+                "project": "Home Stuff, Garden",
+                "annotations": [
+                    {"entry": "20250102T120000Z", "description": "Bring fire resistance"}
+                ]
+            }]"#,
+        );
+
+        assert_eq!(quests[0].tags, ["boss", "Home-Stuff-Garden"]);
+        assert_eq!(quests[0].notes.len(), 1);
+        assert_eq!(quests[0].notes[0].text, "Bring fire resistance");
+        assert_eq!(quests[0].notes[0].created_at, at("2025-01-02T12:00:00Z"));
+    }
+
+    #[test]
+    fn reports_attributes_that_are_not_mapped() {
+        let (_, unmapped) = read_str(
+            r#"[
+                {"uuid": "a", "description": "Slay the dragon", "recur": "weekly", "until": "x"},
+                {"uuid": "b", "description": "Forge a sword", "urgency": 3.5, "id": 2}
+            ]"#,
+        );
+
+        assert_eq!(unmapped, ["task a `Slay the dragon`: recur, until"]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::Cursor;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+
+use crate::date;
+use crate::error::QuestError;
+use crate::export::{NoteRecord, QuestRecord, TransitionRecord};
+use crate::migration;
+use crate::quest::{Quest, QuestDao, Status, Tier};
+use crate::undo::UndoDao;
+
+/// Opens an empty quest log in memory, with the latest schema and foreign keys enforced.
+pub fn open() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", true).unwrap();
+    migration::migrate(&mut conn).unwrap();
+    conn
+}
+
+/// Adds a pending quest as one undoable operation, and returns its identifier.
+pub fn add(conn: &Connection, objective: &str, chain_id: Option<i64>) -> i64 {
+    let quest = Quest::new(
+        objective.to_owned(),
+        Status::Pending,
+        Tier::Common,
+        chain_id,
+    );
+    let recording = UndoDao::new(conn).begin("add a quest").unwrap();
+    let quest = QuestDao::new(conn).add_quest(&quest).unwrap();
+    recording.commit().unwrap();
+    quest.id()
+}
+
+/// Parses a timestamp, such as `2025-01-01T12:00:00Z`.
+pub fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+/// Builds a quest record created, and last updated, at noon on the first day of 2025. The
+/// objective holds the punctuation that the interchange formats escape or split on.
+pub fn record(id: i64, parent_id: Option<i64>, status: Status, tier: Tier) -> QuestRecord {
+    let created_at = at("2025-01-01T12:00:00Z");
+
+    QuestRecord {
+        id,
+        parent_id,
+        objective: format!("Quest {} at 10:30; with, punctuation\\", id),
+        status,
+        tier,
+        due: None,
+        start: None,
+        created_at: Some(created_at),
+        updated_at: Some(created_at),
+        tags: Vec::new(),
+        notes: Vec::new(),
+        history: Vec::new(),
+        quests: None,
+    }
+}
+
+/// Builds a quest chain covering every status and tier, three levels deep, followed by a second
+/// main quest. Only the first quest has dates, tags, and a note.
+pub fn chain() -> Vec<QuestRecord> {
+    let mut main = record(1, None, Status::Ongoing, Tier::Legendary);
+    main.due = NaiveDate::from_ymd_opt(2025, 3, 14);
+    main.start = NaiveDate::from_ymd_opt(2025, 2, 1);
+    main.tags = vec!["forge".to_owned(), "@town".to_owned()];
+    main.notes = vec![NoteRecord {
+        created_at: at("2025-01-01T12:00:00Z"),
+        text: "Bring fire resistance;\nand a spare sword, just in case.".to_owned(),
+    }];
+
+    vec![
+        main,
+        record(2, Some(1), Status::Completed, Tier::Epic),
+        record(3, Some(2), Status::Abandoned, Tier::Rare),
+        record(4, Some(1), Status::Waiting, Tier::Common),
+        record(5, None, Status::Pending, Tier::Common),
+    ]
+}
+
+/// Builds a quest completed on 2 February 2025, then edited on 5 May 2025.
+pub fn edited_after_completion() -> QuestRecord {
+    let mut quest = record(1, None, Status::Completed, Tier::Common);
+    quest.updated_at = Some(at("2025-05-05T12:00:00Z"));
+    quest.history = vec![
+        TransitionRecord {
+            status: Status::Pending,
+            changed_at: at("2025-01-01T12:00:00Z"),
+        },
+        TransitionRecord {
+            status: Status::Completed,
+            changed_at: at("2025-02-02T12:00:00Z"),
+        },
+    ];
+    quest
+}
+
+/// Reads quests written by a codec.
+type Reader = fn(Cursor<Vec<u8>>) -> Result<Vec<QuestRecord>, QuestError>;
+
+/// Writes quests with a codec, and reads them back.
+pub fn round_trip(
+    write: fn(&mut Vec<u8>, &[QuestRecord]) -> Result<(), QuestError>,
+    read: Reader,
+    quests: &[QuestRecord],
+) -> Vec<QuestRecord> {
+    let mut output = Vec::new();
+    write(&mut output, quests).unwrap();
+    read(Cursor::new(output)).unwrap()
+}
+
+/// Asserts that the quests read back match the quests written in every field kept by all of the
+/// line-based formats. Timestamps only need to fall on the same local date.
+pub fn assert_same_quests(read: &[QuestRecord], quests: &[QuestRecord]) {
+    assert_eq!(read.len(), quests.len());
+
+    for (read, quest) in read.iter().zip(quests) {
+        assert_eq!(read.id, quest.id);
+        assert_eq!(read.parent_id, quest.parent_id);
+        assert_eq!(read.objective, quest.objective);
+        assert_eq!(read.status, quest.status);
+        assert_eq!(read.tier, quest.tier);
+        assert_eq!(read.due, quest.due);
+        assert_eq!(read.start, quest.start);
+        assert_eq!(read.tags, quest.tags);
+        assert_eq!(
+            read.created_at.map(date::local_date),
+            quest.created_at.map(date::local_date)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+
+use crate::date::{self, DATE_FORMAT};
+use crate::error::QuestError;
+use crate::export::QuestRecord;
+use crate::quest::{Status, Tier};
+
+/// Writes quests as todo.txt lines, with parents before their secondary quests. Tiers become
+/// priorities, completed and abandoned quests are marked done with their completion date, tags
+/// become projects, or contexts if they start with `@`, and parents are linked with `parent:`.
+pub fn write(writer: &mut impl Write, quests: &[QuestRecord]) -> Result<(), QuestError> {
+    for quest in quests {
+        let mut words = Vec::new();
+        let is_done = matches!(quest.status, Status::Completed | Status::Abandoned);
+        let closed_at = quest.closed_at().filter(|_| is_done);
+
+        if is_done {
+            words.push("x".to_owned());
+            if let Some(closed_at) = closed_at {
+                words.push(date::local_date(closed_at).format(DATE_FORMAT).to_string());
+            }
+        } else {
+            words.push(format!("({})", priority(quest.tier)));
+        }
+
+        // The only date of a done task would be read as its completion date, so a done task
+        // without one has no creation date either.
+        if let Some(created_at) = quest.created_at.filter(|_| !is_done || closed_at.is_some()) {
+            words.push(date::local_date(created_at).format(DATE_FORMAT).to_string());
+        }
+
+        words.push(quest.objective.clone());
+        words.extend(quest.tags.iter().map(|tag| {
+            if tag.starts_with('@') {
+                tag.clone()
+            } else {
+                format!("+{}", tag)
+            }
+        }));
+
+        // Done tasks lose their priority, so it is kept as a key instead.
+        if is_done {
+            words.push(format!("pri:{}", priority(quest.tier)));
+        }
+        if !matches!(quest.status, Status::Pending | Status::Completed) {
+            words.push(format!(
+                "status:{}",
+                quest.status.to_string().to_lowercase()
+            ));
+        }
+        if let Some(due) = quest.due {
+            words.push(format!("due:{}", due.format(DATE_FORMAT)));
+        }
+        if let Some(start) = quest.start {
+            words.push(format!("t:{}", start.format(DATE_FORMAT)));
+        }
+        words.push(format!("id:{}", quest.id));
+        if let Some(parent_id) = quest.parent_id {
+            words.push(format!("parent:{}", parent_id));
+        }
+
+        writeln!(writer, "{}", words.join(" "))?;
+    }
+
+    Ok(())
+}
+
+/// Reads todo.txt lines as quests, reversing `write`. Tasks without a priority are common, and
+/// tasks without an `id:` are identified by their negated line number, so that they never clash
+/// with the identifiers of other tasks. Unknown `key:value` pairs are kept in the objective.
+pub fn read(reader: impl Read) -> Result<Vec<QuestRecord>, QuestError> {
+    let mut quests = Vec::new();
+
+    for (idx, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let quest = parse_line(&line, -(idx as i64 + 1))
+            .map_err(|err| QuestError::InvalidImport(format!("line {}: {}", idx + 1, err)))?;
+        quests.push(quest);
+    }
+
+    Ok(quests)
+}
+
+/// Parses one todo.txt line.
+fn parse_line(line: &str, default_id: i64) -> Result<QuestRecord, String> {
+    let mut words = line.split_whitespace().peekable();
+
+    let is_done = words.next_if_eq(&"x").is_some();
+    let completed_on = words
+        .next_if(|word| parse_date(word).is_ok())
+        .and_then(|word| parse_date(word).ok());
+    let mut tier = words
+        .next_if(|word| parse_priority(word).is_some())
+        .and_then(parse_priority);
+    let created_on = words
+        .next_if(|word| parse_date(word).is_ok())
+        .and_then(|word| parse_date(word).ok());
+
+    // The only date of a pending task is its creation date.
+    let (completed_on, created_on) = match (is_done, completed_on, created_on) {
+        (false, Some(created_on), None) => (None, Some(created_on)),
+        (_, completed_on, created_on) => (completed_on, created_on),
+    };
+
+    let mut quest = QuestRecord {
+        id: default_id,
+        parent_id: None,
+        objective: String::new(),
+        status: if is_done {
+            Status::Completed
+        } else {
+            Status::Pending
+        },
+        tier: Tier::Common,
+        due: None,
+        start: None,
+        created_at: created_on.map(date::start_of_day),
+        updated_at: completed_on.map(date::start_of_day),
+        tags: Vec::new(),
+        notes: Vec::new(),
+        history: Vec::new(),
+        quests: None,
+    };
+    let mut objective = Vec::new();
+
+    for word in words {
+        if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+            quest.tags.push(tag.to_owned());
+            continue;
+        }
+        if word.len() > 1 && word.starts_with('@') {
+            quest.tags.push(word.to_owned());
+            continue;
+        }
+
+        match word.split_once(':') {
+            Some(("id", value)) => quest.id = parse_id(value)?,
+            Some(("parent", value)) => quest.parent_id = Some(parse_id(value)?),
+            Some(("due", value)) => quest.due = Some(parse_date(value)?),
+            Some(("t", value)) => quest.start = Some(parse_date(value)?),
+            Some(("pri", value)) => {
+                tier =
+                    Some(parse_priority(&format!("({})", value)).ok_or_else(|| {
+                        format!("invalid priority `{}`; use A, B, C, or D", value)
+                    })?)
+            }
+            Some(("status", value)) => {
+                quest.status = Status::from_str(value, true)
+                    .map_err(|_| format!("invalid status `{}`", value))?
+            }
+            _ => objective.push(word),
+        }
+    }
+
+    quest.objective = objective.join(" ");
+    quest.tier = tier.unwrap_or(Tier::Common);
+    if quest.objective.is_empty() {
+        return Err("the task has no description".to_owned());
+    }
+
+    Ok(quest)
+}
+
+/// Gets the todo.txt priority of a tier.
+fn priority(tier: Tier) -> char {
+    match tier {
+        Tier::Legendary => 'A',
+        Tier::Epic => 'B',
+        Tier::Rare => 'C',
+        Tier::Common => 'D',
+    }
+}
+
+/// Parses a todo.txt priority, such as `(A)`, into a tier. Priorities below `(D)` are common.
+fn parse_priority(word: &str) -> Option<Tier> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+
+    match letter {
+        "A" => Some(Tier::Legendary),
+        "B" => Some(Tier::Epic),
+        "C" => Some(Tier::Rare),
+        _ if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            Some(Tier::Common)
+        }
+        _ => None,
+    }
+}
+
+/// Parses a todo.txt date.
+fn parse_date(word: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(word, DATE_FORMAT).map_err(|_| format!("invalid date `{}`", word))
+}
+
+/// Parses a task identifier.
+fn parse_id(value: &str) -> Result<i64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid identifier `{}`", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn write_to_string(quests: &[QuestRecord]) -> String {
+        let mut output = Vec::new();
+        write(&mut output, quests).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn parses_done_tasks_with_both_dates() {
+        let quest = parse_line("x 2025-01-05 2025-01-01 Sharpen the sword", 1).unwrap();
+
+        assert_eq!(quest.status, Status::Completed);
+        assert_eq!(quest.updated_at, Some(date::start_of_day(ymd(2025, 1, 5))));
+        assert_eq!(quest.created_at, Some(date::start_of_day(ymd(2025, 1, 1))));
+        assert_eq!(quest.objective, "Sharpen the sword");
+    }
+
+    #[test]
+    fn takes_the_only_date_of_a_pending_task_as_its_creation_date() {
+        let quest = parse_line("(B) 2025-01-01 Sharpen the sword", 1).unwrap();
+
+        assert_eq!(quest.tier, Tier::Epic);
+        assert_eq!(quest.created_at, Some(date::start_of_day(ymd(2025, 1, 1))));
+        assert_eq!(quest.updated_at, None);
+    }
+
+    #[test]
+    fn parses_tags_and_keys() {
+        let line = "(A) Call the smith +forge @town due:2025-03-14 t:2025-03-01 status:waiting \
+            id:7 parent:3 at 10:30 url:https://example.com + @";
+        let quest = parse_line(line, -1).unwrap();
+
+        assert_eq!(quest.id, 7);
+        assert_eq!(quest.parent_id, Some(3));
+        assert_eq!(quest.tier, Tier::Legendary);
+        assert_eq!(quest.status, Status::Waiting);
+        assert_eq!(quest.due, Some(ymd(2025, 3, 14)));
+        assert_eq!(quest.start, Some(ymd(2025, 3, 1)));
+        assert_eq!(quest.tags, ["forge", "@town"]);
+        assert_eq!(
+            quest.objective,
+            "Call the smith at 10:30 url:https://example.com + @"
+        );
+    }
+
+    #[test]
+    fn maps_priorities_to_tiers() {
+        let tier = |line: &str| parse_line(line, 1).unwrap().tier;
+
+        assert_eq!(tier("(C) Task"), Tier::Rare);
+        assert_eq!(tier("(D) Task"), Tier::Common);
+        assert_eq!(tier("(Z) Task"), Tier::Common);
+        assert_eq!(tier("Task"), Tier::Common);
+        assert_eq!(tier("x 2025-01-05 Task pri:A"), Tier::Legendary);
+        // Priorities must be a single capital letter to count.
+        assert_eq!(parse_line("(a) Task", 1).unwrap().objective, "(a) Task");
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in [
+            "(A) +forge @town",
+            "x 2025-01-05",
+            "Task due:soon",
+            "Task id:one",
+            "Task pri:AB",
+            "Task status:lost",
+        ] {
+            assert!(parse_line(line, 1).is_err(), "`{}` parsed", line);
+        }
+    }
+
+    #[test]
+    fn numbers_tasks_without_identifiers_by_line() {
+        let quests = read("First\n\n(A) Third\n".as_bytes()).unwrap();
+
+        let ids: Vec<i64> = quests.iter().map(|quest| quest.id).collect();
+        assert_eq!(ids, [-1, -3]);
+    }
+
+    #[test]
+    fn round_trips_through_write_and_read() {
+        let quests = testing::chain();
+        let read = testing::round_trip(write, read, &quests);
+
+        testing::assert_same_quests(&read, &quests);
+    }
+
+    #[test]
+    fn dates_done_tasks_by_their_last_completion() {
+        let completed_on = date::local_date(testing::at("2025-02-02T12:00:00Z"));
+        let expected = format!("x {} ", completed_on.format(DATE_FORMAT));
+
+        let output = write_to_string(&[testing::edited_after_completion()]);
+        assert!(output.starts_with(&expected));
+    }
+
+    #[test]
+    fn leaves_out_both_dates_of_done_tasks_never_closed() {
+        let mut quest = testing::record(1, None, Status::Abandoned, Tier::Common);
+        quest.updated_at = None;
+
+        let output = write_to_string(&[quest]);
+        assert!(output.starts_with("x Quest 1 "));
+
+        let read = read(output.as_bytes()).unwrap();
+        assert_eq!(read[0].status, Status::Abandoned);
+        assert_eq!(read[0].created_at, None);
+        assert_eq!(read[0].updated_at, None);
+    }
+}
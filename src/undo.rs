@@ -0,0 +1,455 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::database::Transaction;
+use crate::error::QuestError;
+
+/// An undoable command recorded in the undo journal.
+#[derive(Clone, Debug)]
+pub struct Operation {
+    created_at: DateTime<Utc>,
+    description: String,
+}
+
+impl Operation {
+    /// Copies the time the command ran.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Borrows the description of the command.
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+}
+
+/// A table whose row changes are recorded in the undo journal.
+struct JournaledTable {
+    name: &'static str,
+    key: &'static [&'static str],
+    columns: &'static [&'static str],
+}
+
+/// An operation being recorded. Every change made through the connection belongs to the
+/// operation until the recording is committed, and is committed together with it.
+pub struct Recording<'a> {
+    tx: Transaction<'a>,
+}
+
+impl Recording<'_> {
+    /// Stops recording, and commits the operation and its changes.
+    pub fn commit(self) -> Result<(), QuestError> {
+        self.tx.execute(
+            "UPDATE undo_operation SET recording = 0 WHERE recording",
+            [],
+        )?;
+        self.tx.commit()
+    }
+}
+
+/// Records operations in the undo journal, and undoes or redoes them.
+pub struct UndoDao<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> UndoDao<'a> {
+    /// The number of operations kept in the undo journal.
+    const HISTORY_SIZE: i64 = 100;
+
+    /// The tables recorded by the undo triggers, which must list the same columns.
+    const JOURNALED_TABLES: &'static [JournaledTable] = &[
+        JournaledTable {
+            name: "quest",
+            key: &["id"],
+            columns: &[
+                "id",
+                "chain_id",
+                "objective",
+                "status",
+                "tier",
+                "due_date",
+                "start_date",
+                "created_at",
+                "updated_at",
+                "deleted_at",
+            ],
+        },
+        JournaledTable {
+            name: "quest_transition",
+            key: &["id"],
+            columns: &["id", "quest_id", "status", "changed_at"],
+        },
+        JournaledTable {
+            name: "tag",
+            key: &["id"],
+            columns: &["id", "name"],
+        },
+        JournaledTable {
+            name: "quest_tag",
+            key: &["quest_id", "tag_id"],
+            columns: &["quest_id", "tag_id"],
+        },
+        JournaledTable {
+            name: "note",
+            key: &["id"],
+            columns: &["id", "quest_id", "text", "created_at"],
+        },
+    ];
+
+    /// Constructs a new undo data access object.
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Begins recording an operation. Recording a new operation forgets every undone operation,
+    /// which can no longer be redone, and the oldest operations beyond the history size.
+    pub fn begin(&self, description: &str) -> Result<Recording<'a>, QuestError> {
+        let tx = Transaction::new(self.conn)?;
+
+        tx.execute("DELETE FROM undo_operation WHERE undone_at IS NOT NULL", [])?;
+        tx.execute(
+            "DELETE FROM undo_operation WHERE id NOT IN (
+                SELECT id FROM undo_operation ORDER BY id DESC LIMIT ?1
+            )",
+            [Self::HISTORY_SIZE - 1],
+        )?;
+        tx.execute(
+            "INSERT INTO undo_operation (description, created_at, recording) VALUES (?1, ?2, 1)",
+            params![description, Utc::now()],
+        )?;
+
+        Ok(Recording { tx })
+    }
+
+    /// Undoes the latest operation that has not been undone, and returns it. Returns `None` if
+    /// there is nothing to undo.
+    pub fn undo(&self) -> Result<Option<Operation>, QuestError> {
+        self.replay(
+            "SELECT id, description, created_at FROM undo_operation
+            WHERE undone_at IS NULL ORDER BY id DESC LIMIT 1",
+            "SELECT table_name, after, before FROM undo_change
+            WHERE operation_id = ?1 ORDER BY id DESC",
+            Some(Utc::now()),
+        )
+    }
+
+    /// Redoes the earliest undone operation, and returns it. Returns `None` if there is nothing to
+    /// redo.
+    pub fn redo(&self) -> Result<Option<Operation>, QuestError> {
+        self.replay(
+            "SELECT id, description, created_at FROM undo_operation
+            WHERE undone_at IS NOT NULL ORDER BY id LIMIT 1",
+            "SELECT table_name, before, after FROM undo_change
+            WHERE operation_id = ?1 ORDER BY id",
+            None,
+        )
+    }
+
+    /// Replays the changes of the operation selected by a query, turning each row from one image
+    /// into the other, then stores when the operation was undone, if it was.
+    fn replay(
+        &self,
+        operation_query: &str,
+        changes_query: &str,
+        undone_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Operation>, QuestError> {
+        let tx = Transaction::new(self.conn)?;
+
+        // Rows are restored one at a time, so parents may briefly be missing.
+        tx.pragma_update(None, "defer_foreign_keys", true)?;
+
+        let operation = tx
+            .query_row(operation_query, [], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    Operation {
+                        description: row.get(1)?,
+                        created_at: row.get(2)?,
+                    },
+                ))
+            })
+            .optional()?;
+
+        let Some((operation_id, operation)) = operation else {
+            return Ok(None);
+        };
+
+        let changes = tx
+            .prepare(changes_query)?
+            .query_map([operation_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(String, Option<String>, Option<String>)>>>()?;
+
+        for (table_name, from, to) in changes {
+            Self::apply(&tx, &table_name, from.as_deref(), to.as_deref())?;
+        }
+
+        tx.execute(
+            "UPDATE undo_operation SET undone_at = ?2 WHERE id = ?1",
+            params![operation_id, undone_at],
+        )?;
+        tx.commit()?;
+
+        Ok(Some(operation))
+    }
+
+    /// Changes a row from one image to the other. A missing image inserts or deletes the row.
+    fn apply(
+        conn: &Connection,
+        table_name: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<(), QuestError> {
+        let table = Self::JOURNALED_TABLES
+            .iter()
+            .find(|table| table.name == table_name)
+            .ok_or_else(|| {
+                QuestError::StorageFailure(format!("unknown journaled table `{}`", table_name))
+            })?;
+
+        let key: Vec<String> = table
+            .key
+            .iter()
+            .map(|column| format!("{} = json_extract(?1, '$.{}')", column, column))
+            .collect();
+
+        match (from, to) {
+            (None, Some(to)) => {
+                let values: Vec<String> = table
+                    .columns
+                    .iter()
+                    .map(|column| format!("json_extract(?1, '$.{}')", column))
+                    .collect();
+                let query = format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table.name,
+                    table.columns.join(", "),
+                    values.join(", ")
+                );
+                conn.execute(&query, [to])?;
+            }
+            (Some(from), None) => {
+                let query = format!("DELETE FROM {} WHERE {}", table.name, key.join(" AND "));
+                conn.execute(&query, [from])?;
+            }
+            (Some(from), Some(to)) => {
+                let values: Vec<String> = table
+                    .columns
+                    .iter()
+                    .map(|column| format!("{} = json_extract(?2, '$.{}')", column, column))
+                    .collect();
+                let query = format!(
+                    "UPDATE {} SET {} WHERE {}",
+                    table.name,
+                    values.join(", "),
+                    key.join(" AND ")
+                );
+                conn.execute(&query, [from, to])?;
+            }
+            (None, None) => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quest::{QuestDao, Status};
+    use crate::testing::{add, open};
+
+    /// Runs a change as one recorded operation.
+    fn record(conn: &Connection, description: &str, change: impl FnOnce(&QuestDao)) {
+        let recording = UndoDao::new(conn).begin(description).unwrap();
+        change(&QuestDao::new(conn));
+        recording.commit().unwrap();
+    }
+
+    fn tags(conn: &Connection, quest_id: i64) -> Vec<String> {
+        QuestDao::new(conn)
+            .get_quest(quest_id)
+            .unwrap()
+            .tags()
+            .clone()
+    }
+
+    fn count(conn: &Connection, table_name: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT() FROM {}", table_name), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn undoes_and_redoes_an_added_quest() {
+        let conn = open();
+        let quest_id = add(&conn, "Slay the dragon", None);
+        let undo_dao = UndoDao::new(&conn);
+
+        let operation = undo_dao.undo().unwrap().unwrap();
+        assert_eq!(operation.description(), "add a quest");
+        assert_eq!(count(&conn, "quest"), 0);
+        assert_eq!(count(&conn, "quest_transition"), 0);
+        assert!(undo_dao.undo().unwrap().is_none());
+
+        undo_dao.redo().unwrap().unwrap();
+        let quest = QuestDao::new(&conn).get_quest(quest_id).unwrap();
+        assert_eq!(quest.objective(), "Slay the dragon");
+        assert_eq!(count(&conn, "quest_transition"), 1);
+        assert!(undo_dao.redo().unwrap().is_none());
+    }
+
+    #[test]
+    fn undoes_and_redoes_changed_tags() {
+        let conn = open();
+        let quest_id = add(&conn, "Slay the dragon", None);
+        record(&conn, "tag a quest", |quest_dao| {
+            let mut quest = quest_dao.get_quest(quest_id).unwrap();
+            *quest.tags_mut() = vec!["boss".to_owned(), "fire".to_owned()];
+            *quest.status_mut() = Status::Ongoing;
+            quest_dao.update_quest(&quest).unwrap();
+        });
+        record(&conn, "retag a quest", |quest_dao| {
+            let mut quest = quest_dao.get_quest(quest_id).unwrap();
+            *quest.tags_mut() = vec!["fire".to_owned(), "lair".to_owned()];
+            quest_dao.update_quest(&quest).unwrap();
+        });
+        let undo_dao = UndoDao::new(&conn);
+
+        // Unused tags are deleted along the way, and must come back with their links.
+        undo_dao.undo().unwrap().unwrap();
+        assert_eq!(tags(&conn, quest_id), ["boss", "fire"]);
+        assert_eq!(count(&conn, "tag"), 2);
+
+        undo_dao.undo().unwrap().unwrap();
+        assert!(tags(&conn, quest_id).is_empty());
+        assert_eq!(count(&conn, "tag"), 0);
+        let quest = QuestDao::new(&conn).get_quest(quest_id).unwrap();
+        assert_eq!(quest.status(), Status::Pending);
+        assert_eq!(count(&conn, "quest_transition"), 1);
+
+        undo_dao.redo().unwrap().unwrap();
+        undo_dao.redo().unwrap().unwrap();
+        assert_eq!(tags(&conn, quest_id), ["fire", "lair"]);
+        assert_eq!(count(&conn, "tag"), 2);
+        assert_eq!(count(&conn, "quest_transition"), 2);
+    }
+
+    #[test]
+    fn undoes_and_redoes_a_deleted_chain() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let secondary_id = add(&conn, "Forge a sword", Some(main_id));
+        record(&conn, "delete a quest", |quest_dao| {
+            quest_dao.delete_chain(main_id).unwrap();
+        });
+        let undo_dao = UndoDao::new(&conn);
+        let quest_dao = QuestDao::new(&conn);
+
+        undo_dao.undo().unwrap().unwrap();
+        assert!(quest_dao.get_trash().unwrap().is_empty());
+        assert_eq!(quest_dao.get_all_chains().unwrap()[0].chains().len(), 1);
+
+        undo_dao.redo().unwrap().unwrap();
+        let trash = quest_dao.get_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].0.id(), main_id);
+        assert_eq!(trash[0].1, 2);
+        assert!(quest_dao.get_quest(secondary_id).is_err());
+        assert!(quest_dao.get_all_chains().unwrap().is_empty());
+    }
+
+    #[test]
+    fn undoes_and_redoes_a_moved_quest() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let quest_id = add(&conn, "Forge a sword", None);
+        record(&conn, "move a quest", |quest_dao| {
+            quest_dao.move_quest(quest_id, Some(main_id)).unwrap();
+        });
+        let undo_dao = UndoDao::new(&conn);
+        let quest_dao = QuestDao::new(&conn);
+
+        undo_dao.undo().unwrap().unwrap();
+        assert_eq!(quest_dao.get_quest(quest_id).unwrap().chain_id(), None);
+
+        undo_dao.redo().unwrap().unwrap();
+        assert_eq!(
+            quest_dao.get_quest(quest_id).unwrap().chain_id(),
+            Some(main_id)
+        );
+    }
+
+    #[test]
+    fn a_new_operation_forgets_undone_operations() {
+        let conn = open();
+        add(&conn, "Slay the dragon", None);
+        add(&conn, "Forge a sword", None);
+        let undo_dao = UndoDao::new(&conn);
+
+        undo_dao.undo().unwrap().unwrap();
+        add(&conn, "Find the lair", None);
+
+        assert!(undo_dao.redo().unwrap().is_none());
+        assert_eq!(count(&conn, "undo_operation"), 2);
+
+        let objectives: Vec<String> = QuestDao::new(&conn)
+            .get_all_chains()
+            .unwrap()
+            .iter()
+            .map(|chain| chain.objective().clone())
+            .collect();
+        assert_eq!(objectives, ["Slay the dragon", "Find the lair"]);
+    }
+
+    #[test]
+    fn replays_rows_that_refer_to_each_other() {
+        let conn = open();
+        let main_id = add(&conn, "Slay the dragon", None);
+        let secondary_id = add(&conn, "Forge a sword", Some(main_id));
+        record(&conn, "tag a quest", |quest_dao| {
+            let mut quest = quest_dao.get_quest(secondary_id).unwrap();
+            quest.tags_mut().push("boss".to_owned());
+            quest_dao.update_quest(&quest).unwrap();
+        });
+
+        // Delete the chain for good, children first, as the foreign keys require.
+        record(&conn, "purge a chain", |_| {
+            conn.execute_batch(
+                "DELETE FROM quest_tag;
+                DELETE FROM tag;
+                DELETE FROM quest_transition;
+                DELETE FROM quest WHERE chain_id IS NOT NULL;
+                DELETE FROM quest;",
+            )
+            .unwrap();
+        });
+        let undo_dao = UndoDao::new(&conn);
+
+        undo_dao.undo().unwrap().unwrap();
+        let chains = QuestDao::new(&conn).get_all_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].chains()[0].main().id(), secondary_id);
+        assert_eq!(tags(&conn, secondary_id), ["boss"]);
+        assert_eq!(count(&conn, "quest_transition"), 2);
+
+        undo_dao.redo().unwrap().unwrap();
+        assert_eq!(count(&conn, "quest"), 0);
+        assert_eq!(count(&conn, "tag"), 0);
+
+        // Undoing everything replays each insertion backwards, children before their parents.
+        while undo_dao.undo().unwrap().is_some() {}
+        assert_eq!(count(&conn, "quest"), 0);
+
+        while undo_dao.redo().unwrap().is_some() {}
+        assert_eq!(count(&conn, "quest"), 0);
+        let violations: i64 = conn
+            .query_row("SELECT COUNT() FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(violations, 0);
+    }
+}